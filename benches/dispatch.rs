@@ -0,0 +1,149 @@
+//! Compares the dispatch throughput of the array-indexed `SubscriptionTable`
+//! against the `HashMap<MessageType, HashSet<ActorId>>` that the commutator
+//! used before.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use std::collections::{HashMap, HashSet};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use armature::{Actor, ActorId, Commutator, Envelope, MessageType};
+use armature::{InterceptResult, SubscriptionTable};
+
+const ACTORS: usize = 64;
+const ROUNDS: usize = 1_000_000;
+
+#[allow(dead_code)]
+#[derive(Debug, MessageType)]
+#[message_type(name = "Signal")]
+pub enum Event {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    Stop,
+}
+
+impl armature::Message for Event {
+    type MessageType = Signal;
+}
+
+impl armature::MessageType for Signal {
+    type Message = Event;
+}
+
+/// Every actor subscribes to a few message types, spread over all of them.
+fn subscriptions(actor: usize) -> impl Iterator<Item = Signal> {
+    let count = Signal::COUNT - 1;
+    (0..3).map(move |offset| Signal::ALL[(actor * 5 + offset) % count])
+}
+
+fn report(name: &str, elapsed: Duration, operations: usize) {
+    let per_operation = elapsed.as_nanos() as f64 / operations as f64;
+    println!(
+        "{:<32} {:>10.2?} total {:>8.2} ns/dispatch",
+        name, elapsed, per_operation
+    );
+}
+
+fn bench_hash_map() {
+    let mut map: HashMap<Signal, HashSet<ActorId>> = HashMap::new();
+    for actor in 0..ACTORS {
+        for sig in subscriptions(actor) {
+            map.entry(sig).or_default().insert(actor);
+        }
+    }
+    let start = Instant::now();
+    let mut sum = 0;
+    for round in 0..ROUNDS {
+        let sig = Signal::ALL[round % (Signal::COUNT - 1)];
+        if let Some(subscribers) = map.get(black_box(&sig)) {
+            for id in subscribers.iter() {
+                sum += black_box(*id);
+            }
+        }
+    }
+    black_box(sum);
+    report("HashMap<_, HashSet<ActorId>>", start.elapsed(), ROUNDS);
+}
+
+fn bench_subscription_table() {
+    let mut table = SubscriptionTable::<Signal>::new();
+    for actor in 0..ACTORS {
+        for sig in subscriptions(actor) {
+            table.insert(sig, actor);
+        }
+    }
+    let start = Instant::now();
+    let mut sum = 0;
+    for round in 0..ROUNDS {
+        let sig = Signal::ALL[round % (Signal::COUNT - 1)];
+        for id in table.subscribers(black_box(sig)) {
+            sum += black_box(*id);
+        }
+    }
+    black_box(sum);
+    report("SubscriptionTable", start.elapsed(), ROUNDS);
+}
+
+struct Counter {
+    subscriptions: Vec<Signal>,
+    count: usize,
+}
+
+impl Actor for Counter {
+    type Message = Event;
+
+    fn handle(&mut self, _: &Envelope<Event>) {
+        self.count += 1;
+    }
+
+    fn default_subscriptions(&self) -> Vec<Signal> {
+        self.subscriptions.clone()
+    }
+}
+
+fn bench_commutator() {
+    let mut commutator = Commutator::new();
+    for actor in 0..ACTORS {
+        commutator.attach(Box::new(Counter {
+            subscriptions: subscriptions(actor).collect(),
+            count: 0,
+        }));
+    }
+    commutator.set_interceptor(|_, message| match message {
+        Event::Stop => InterceptResult::Break,
+        message => InterceptResult::Pass(message),
+    });
+    let rounds = ROUNDS / 10;
+    for round in 0..rounds {
+        let sig = Signal::ALL[round % (Signal::COUNT - 1)];
+        let message = match sig {
+            Signal::A => Event::A,
+            Signal::B => Event::B,
+            Signal::C => Event::C,
+            Signal::D => Event::D,
+            Signal::E => Event::E,
+            Signal::F => Event::F,
+            Signal::G => Event::G,
+            Signal::H => Event::H,
+            Signal::Stop => Event::Stop,
+        };
+        commutator.publish(message);
+    }
+    commutator.publish(Event::Stop);
+    let start = Instant::now();
+    async_std::task::block_on(commutator.run());
+    report("Commutator::run", start.elapsed(), rounds);
+}
+
+fn main() {
+    bench_hash_map();
+    bench_subscription_table();
+    bench_commutator();
+}
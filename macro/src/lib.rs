@@ -5,7 +5,7 @@ use quote::format_ident;
 use quote::quote;
 use syn;
 use syn::parse_macro_input;
-use syn::{Meta::NameValue, NestedMeta::Meta};
+use syn::{Meta::NameValue, Meta::Path, NestedMeta::Meta};
 
 #[proc_macro_derive(MessageType, attributes(message_type))]
 pub fn derive_message_type(input: TokenStream) -> TokenStream {
@@ -18,22 +18,42 @@ pub fn derive_message_type(input: TokenStream) -> TokenStream {
 
     let message_name = &message_enum.ident;
     let message_variants = &message_enum.variants;
-    let message_type_name = match parse_message_type_attribute(&message_enum.attrs) {
-        Some(meta_items) => meta_items.iter().find_map(|meta_item| match meta_item {
-            Meta(NameValue(name_value)) if name_value.path.is_ident("name") => {
-                match &name_value.lit {
-                    syn::Lit::Str(name_lit) => Some(format_ident!("{}", name_lit.value())),
-                    _ => panic!("name must be a string literal"),
-                }
-            }
-            _ => panic!("message type attribute must be a list"),
-        }),
+    let container_attributes = match parse_message_type_attribute(&message_enum.attrs) {
+        Some(meta_items) => parse_container_attributes(meta_items),
+        None => panic!(r#"#[message_type(name = "<signal_name>") attribute required"#),
+    };
+    let message_type_name = match container_attributes.name {
+        Some(name) => name,
         None => panic!(r#"#[message_type(name = "<signal_name>") attribute required"#),
     };
+    let serde_derive = if container_attributes.serde {
+        quote! {
+            #[derive(::armature::serde::Serialize, ::armature::serde::Deserialize)]
+            #[serde(crate = "::armature::serde")]
+        }
+    } else {
+        quote!()
+    };
 
-    let message_type_variants = message_variants
-        .into_iter()
-        .map(|v| format_ident!("{}", v.ident));
+    let message_type_variants: Vec<_> = message_variants
+        .iter()
+        .map(|v| format_ident!("{}", v.ident))
+        .collect();
+    let message_type_count = message_type_variants.len();
+    let message_type_names = message_type_variants.iter().map(|v| v.to_string());
+    let variant_attributes: Vec<_> = message_variants
+        .iter()
+        .map(|v| parse_variant_attributes(&v.attrs))
+        .collect();
+    let message_type_priorities = variant_attributes.iter().map(|attributes| {
+        let priority = &attributes.priority;
+        quote!(::armature::message::Priority::#priority)
+    });
+    let message_type_groups = variant_attributes.iter().map(|attributes| {
+        let groups = &attributes.groups;
+        quote!(&[#(#groups),*])
+    });
+    let message_type_skipped = variant_attributes.iter().map(|attributes| attributes.skip);
     let message_type_variant_match_arms = message_variants.into_iter().map(|v| {
         //let message_type_variant_name = format_ident!("{}Sig", v.ident);
         let message_type_variant_name = &v.ident;
@@ -48,10 +68,86 @@ pub fn derive_message_type(input: TokenStream) -> TokenStream {
     let gen = quote! {
 
         #[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+        #serde_derive
         pub enum #message_type_name {
             #(#message_type_variants),*
         }
 
+        impl #message_type_name {
+            /// All the message types, in declaration order.
+            pub const ALL: &'static [Self] = &[#(Self::#message_type_variants),*];
+
+            /// The number of message types.
+            pub const COUNT: usize = #message_type_count;
+
+            /// Get the name of the message type.
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    #(Self::#message_type_variants => #message_type_names),*
+                }
+            }
+
+            /// Get the dense index of the message type, which is its position
+            /// in `ALL`.
+            pub fn index(&self) -> usize {
+                *self as usize
+            }
+
+            /// Get the priority of the message type, as set by the
+            /// `#[message_type(priority = "...")]` variant attribute.
+            pub fn priority(&self) -> ::armature::message::Priority {
+                match *self {
+                    #(Self::#message_type_variants => #message_type_priorities),*
+                }
+            }
+
+            /// Get the groups of the message type, as set by the
+            /// `#[message_type(group = "...")]` variant attributes.
+            pub fn groups(&self) -> &'static [&'static str] {
+                match *self {
+                    #(Self::#message_type_variants => #message_type_groups),*
+                }
+            }
+
+            /// Check if the message type is in the given group.
+            pub fn in_group(&self, group: &str) -> bool {
+                self.groups().contains(&group)
+            }
+
+            /// Check if the message type is excluded from subscriptions with
+            /// the `#[message_type(skip)]` variant attribute.
+            pub fn is_skipped(&self) -> bool {
+                match *self {
+                    #(Self::#message_type_variants => #message_type_skipped),*
+                }
+            }
+        }
+
+        impl ::armature::message::MessageTypeInfo for #message_type_name {
+            const ALL: &'static [Self] = Self::ALL;
+            const COUNT: usize = Self::COUNT;
+
+            fn name(&self) -> &'static str {
+                Self::name(self)
+            }
+
+            fn index(&self) -> usize {
+                Self::index(self)
+            }
+
+            fn priority(&self) -> ::armature::message::Priority {
+                Self::priority(self)
+            }
+
+            fn groups(&self) -> &'static [&'static str] {
+                Self::groups(self)
+            }
+
+            fn is_skipped(&self) -> bool {
+                Self::is_skipped(self)
+            }
+        }
+
         impl From<&#message_name> for #message_type_name {
             fn from(message: &#message_name) -> Self {
                 match message {
@@ -64,16 +160,91 @@ pub fn derive_message_type(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
-fn parse_message_type_attribute(attrs: &Vec<syn::Attribute>) -> Option<Vec<syn::NestedMeta>> {
-    let state_attr = attrs.iter().find(|attr| attr.path.is_ident("message_type"));
-    let state_attr = match state_attr {
-        Some(attr) => attr,
-        None => return None,
+/// Collect the items of all the `message_type` attributes, so they can be
+/// spread over several attributes, for example behind a `cfg_attr`.
+fn parse_message_type_attribute(attrs: &[syn::Attribute]) -> Option<Vec<syn::NestedMeta>> {
+    let mut state_attrs = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("message_type"))
+        .peekable();
+    state_attrs.peek()?;
+
+    let mut meta_items = Vec::new();
+    for state_attr in state_attrs {
+        match state_attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => meta_items.extend(list.nested),
+            Ok(_) => panic!("signal attribute must be a list"),
+            Err(_) => panic!("signal attribute must follow meta syntax"),
+        }
+    }
+    Some(meta_items)
+}
+
+/// Attributes that can be set on the message enum itself.
+struct ContainerAttributes {
+    name: Option<syn::Ident>,
+    serde: bool,
+}
+
+fn parse_container_attributes(meta_items: Vec<syn::NestedMeta>) -> ContainerAttributes {
+    let mut attributes = ContainerAttributes {
+        name: None,
+        serde: false,
     };
+    for meta_item in meta_items {
+        match meta_item {
+            Meta(NameValue(name_value)) if name_value.path.is_ident("name") => {
+                attributes.name = match &name_value.lit {
+                    syn::Lit::Str(name_lit) => Some(format_ident!("{}", name_lit.value())),
+                    _ => panic!("name must be a string literal"),
+                }
+            }
+            Meta(Path(path)) if path.is_ident("serde") => attributes.serde = true,
+            _ => panic!("message type attribute must be `name` or `serde`"),
+        }
+    }
+    attributes
+}
+
+/// Attributes that can be set on the individual variants of the message enum.
+struct VariantAttributes {
+    priority: syn::Ident,
+    skip: bool,
+    groups: Vec<String>,
+}
 
-    match state_attr.parse_meta() {
-        Ok(syn::Meta::List(meta_items)) => Some(meta_items.nested.into_iter().collect()),
-        Ok(_) => panic!("signal attribute must be a list"),
-        Err(_) => panic!("signal attribute must follow meta syntax"),
+fn parse_variant_attributes(attrs: &[syn::Attribute]) -> VariantAttributes {
+    let mut attributes = VariantAttributes {
+        priority: format_ident!("Normal"),
+        skip: false,
+        groups: Vec::new(),
+    };
+    let meta_items = match parse_message_type_attribute(attrs) {
+        Some(meta_items) => meta_items,
+        None => return attributes,
+    };
+    for meta_item in meta_items {
+        match meta_item {
+            Meta(Path(path)) if path.is_ident("skip") => attributes.skip = true,
+            Meta(NameValue(name_value)) if name_value.path.is_ident("priority") => {
+                attributes.priority = match &name_value.lit {
+                    syn::Lit::Str(priority_lit) => match priority_lit.value().as_str() {
+                        "low" => format_ident!("Low"),
+                        "normal" => format_ident!("Normal"),
+                        "high" => format_ident!("High"),
+                        _ => panic!(r#"priority must be "low", "normal" or "high""#),
+                    },
+                    _ => panic!("priority must be a string literal"),
+                }
+            }
+            Meta(NameValue(name_value)) if name_value.path.is_ident("group") => {
+                match &name_value.lit {
+                    syn::Lit::Str(group_lit) => attributes.groups.push(group_lit.value()),
+                    _ => panic!("group must be a string literal"),
+                }
+            }
+            _ => panic!("variant attribute must be `skip`, `priority` or `group`"),
+        }
     }
+    attributes
 }
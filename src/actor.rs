@@ -1,31 +1,156 @@
+use std::any::{Any, TypeId};
+use std::cell::Cell;
 use std::collections::HashSet;
-use std::fmt::Debug;
+use std::fmt;
 
+use crate::actor_ref::ActorRef;
 use crate::message::*;
+use crate::publisher::DeputyPublisher;
+use crate::subscription::{Subscription, SubscriptionFilter, SubscriptionId};
+use crate::system::SystemMessage;
 use crate::Sender;
 
+/// Identifier of an actor that is attached to a commutator. The commutator
+/// gives every actor it attaches a new id, starting from 1.
+pub type ActorId = usize;
+
+thread_local! {
+    /// The actor the commutator is calling on this thread.
+    static CURRENT: Cell<Option<Called>> = const { Cell::new(None) };
+}
+
+/// An actor the commutator is calling, with the memory the actor occupies, so
+/// other values can't pass for it.
+#[derive(Clone, Copy)]
+pub(crate) struct Called {
+    id: ActorId,
+    start: usize,
+    len: usize,
+    type_id: TypeId,
+}
+
+impl Called {
+    pub(crate) fn new(id: ActorId, actor: &dyn Any) -> Self {
+        Self {
+            id,
+            start: actor as *const dyn Any as *const () as usize,
+            len: std::mem::size_of_val(actor),
+            type_id: actor.type_id(),
+        }
+    }
+
+    /// Check if the value at the given address is the called actor, or lies
+    /// inside it, like the actor an adapter wraps. Actors without a size all
+    /// share an address, so those also have to be of the same type.
+    fn contains(&self, start: usize, len: usize, type_id: TypeId) -> bool {
+        if self.len == 0 {
+            start == self.start && type_id == self.type_id
+        } else {
+            start >= self.start && start + len <= self.start + self.len
+        }
+    }
+}
+
+/// Call into the given actor, so `Actor::id` returns its id.
+pub(crate) fn as_actor<R>(called: Called, call: impl FnOnce() -> R) -> R {
+    /// Restores the previous actor, also if the call panics.
+    struct Restore(Option<Called>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(called))));
+    call()
+}
+
 pub type ActorObject<E> = Box<dyn Actor<Message = E>>;
 
-impl<E: Message> Clone for ActorObject<E> {
-    fn clone(&self) -> ActorObject<E> {
-        todo!();
+/// Boxed actor that can be cloned, for example to attach several copies of a
+/// template actor, or to keep a snapshot of an actor before attaching it. A
+/// `CloneableActorObject` can be passed wherever an `ActorObject` is expected.
+pub type CloneableActorObject<E> = Box<dyn CloneActor<Message = E>>;
+
+/// Actor that can be cloned as a trait object. This trait is implemented for
+/// every actor that implements `Clone`, so actors that can't be cloned can't
+/// be turned into a `CloneableActorObject`:
+///
+/// ```compile_fail
+/// # use armature::{Actor, CloneableActorObject, Envelope, MessageType};
+/// # #[derive(MessageType)]
+/// # #[message_type(name = "Signal")]
+/// # pub enum Event { Tick }
+/// # impl armature::Message for Event { type MessageType = Signal; }
+/// # impl armature::MessageType for Signal { type Message = Event; }
+/// struct NotClone;
+///
+/// impl Actor for NotClone {
+///     type Message = Event;
+///     fn handle(&mut self, _: &Envelope<Event>) {}
+/// }
+///
+/// let actor: CloneableActorObject<Event> = Box::new(NotClone);
+/// ```
+pub trait CloneActor: Actor {
+    /// Clone the actor into a new box.
+    fn clone_actor(&self) -> CloneableActorObject<Self::Message>;
+}
+
+impl<A> CloneActor for A
+where
+    A: Actor + Clone + 'static,
+{
+    fn clone_actor(&self) -> CloneableActorObject<Self::Message> {
+        Box::new(self.clone())
+    }
+}
+
+impl<E: Message> Clone for CloneableActorObject<E> {
+    fn clone(&self) -> CloneableActorObject<E> {
+        self.clone_actor()
     }
 }
 
-pub trait Actor: Send {
+/// A cloneable actor can be used as a template that creates its copies.
+impl<E: Message> ActorFactory<E> for CloneableActorObject<E> {
+    fn create(&mut self) -> ActorObject<E> {
+        self.clone_actor()
+    }
+}
+
+/// Actor that is attached to a commutator and handles the messages that are
+/// dispatched to it. Actors are `Any`, so an attached actor can be downcast to
+/// its concrete type with `Commutator::get_actor` and `Commutator::detach_as`.
+pub trait Actor: Any + Send {
     type Message: Message;
 
     /// Handle event
     fn handle(&mut self, _: &Envelope<Self::Message>);
 
+    /// Handle a notification from the commutator, such as the termination
+    /// of a monitored actor.
+    fn handle_system(&mut self, _: &Envelope<SystemMessage>) {}
+
     /// Lifecycle method that is called when the event handler is attached
     /// to the commutator. The `sender` can be cloned and used to send events
     /// to the commutator.
-    fn on_attach(&mut self, _: &Sender<Self::Message>) {}
+    fn on_attach(&mut self, sender: &Sender<Self::Message>) {
+        if let Some(id) = self.id() {
+            if let Some(component) = self.component_mut() {
+                component.attach(id, sender);
+            }
+        }
+    }
 
     /// Lifecycle method that is called when the event handler is detached from
     /// the commutator.
-    fn on_detach(&mut self) {}
+    fn on_detach(&mut self) {
+        if let Some(component) = self.component_mut() {
+            component.detach();
+        }
+    }
 
     /// Init method that is called when the commutator starts running, or if
     /// the commutator is already running right when the event handler is attached.
@@ -39,47 +164,262 @@ pub trait Actor: Send {
         Vec::new()
     }
 
-    /// Get the id of the event handler.
-    fn id(&self) -> usize {
-        self as *const Self as *const () as usize
+    /// Get the initial subscriptions of the event handler, including
+    /// wildcard, group and predicate subscriptions. By default these are the
+    /// message types returned by `default_subscriptions`.
+    fn initial_subscriptions(&self) -> Vec<Subscription<Self::Message>> {
+        self.default_subscriptions()
+            .into_iter()
+            .map(Subscription::One)
+            .collect()
     }
 
-    /// Insert a subscription.
-    fn insert_subscription(&mut self, _sig: <Self::Message as Message>::MessageType) {}
+    /// Get the filters that are set on the subscriptions of the event handler
+    /// when it is attached. A message of a filtered message type is only
+    /// handed to the event handler if its filter returns `true`.
+    fn subscription_filters(&self) -> Vec<SubscriptionFilter<Self::Message>> {
+        Vec::new()
+    }
 
-    /// Remove a subscription from the `Actor`.
-    fn remove_subscription(&mut self, _sig: <Self::Message as Message>::MessageType) {}
+    /// Get the groups the event handler joins when it is attached.
+    fn default_groups(&self) -> Vec<GroupId> {
+        Vec::new()
+    }
+
+    /// Get the name of the type of the actor, for introspection.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Get the id the commutator gave the event handler when it was attached.
+    /// The id is known through the component of the event handler, if it has
+    /// one, and otherwise only while the commutator calls the event handler.
+    /// Returns `None` if the id isn't known, also for other actors that are
+    /// asked for their id while the commutator calls this one.
+    fn id(&self) -> Option<ActorId> {
+        self.component()
+            .and_then(|component| component.id())
+            .or_else(|| {
+                let start = self as *const Self as *const () as usize;
+                let len = std::mem::size_of_val(self);
+                let type_id = Any::type_id(self);
+                CURRENT
+                    .with(Cell::get)
+                    .filter(|called| called.contains(start, len, type_id))
+                    .map(|called| called.id)
+            })
+    }
+
+    /// Get the component that keeps track of the id, the sender and the
+    /// subscriptions of the actor, if it has one.
+    fn component(&self) -> Option<&HandlerComponent<Self::Message>> {
+        None
+    }
+
+    /// Get the component of the actor mutably, if it has one.
+    fn component_mut(&mut self) -> Option<&mut HandlerComponent<Self::Message>> {
+        None
+    }
+
+    /// Insert a subscription. This is called by the commutator whenever the
+    /// actor is subscribed to a message type.
+    fn insert_subscription(&mut self, sig: <Self::Message as Message>::MessageType) {
+        if let Some(component) = self.component_mut() {
+            component.insert_subscription(sig);
+        }
+    }
+
+    /// Remove a subscription from the `Actor`. This is called by the
+    /// commutator whenever the actor is unsubscribed from a message type.
+    fn remove_subscription(&mut self, sig: <Self::Message as Message>::MessageType) {
+        if let Some(component) = self.component_mut() {
+            component.remove_subscription(sig);
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct HandlerComponent<S>
+/// Creates actors on demand, for example to spawn children of an actor.
+pub trait ActorFactory<M>: Send
 where
-    S: MessageType,
+    M: Message,
 {
-    _id: Option<usize>,
-    subscriptions: HashSet<S>,
+    fn create(&mut self) -> ActorObject<M>;
 }
 
-impl<S> Default for HandlerComponent<S>
+impl<M, F> ActorFactory<M> for F
 where
-    S: MessageType,
+    M: Message,
+    F: FnMut() -> ActorObject<M> + Send,
+{
+    fn create(&mut self) -> ActorObject<M> {
+        self()
+    }
+}
+
+/// Reusable component that keeps track of the id, the sender and the
+/// subscriptions of an actor. An actor that embeds a `HandlerComponent` and
+/// returns it from `Actor::component` and `Actor::component_mut` gets the
+/// default lifecycle and subscription methods wired to it, so the
+/// subscriptions in the component always match what the commutator routes to
+/// the actor.
+///
+/// Actors that override `on_attach` or `on_detach` have to call
+/// `HandlerComponent::attach` and `HandlerComponent::detach` themselves.
+pub struct HandlerComponent<M>
+where
+    M: Message,
+{
+    id: Option<ActorId>,
+    sender: Option<Sender<M>>,
+    subscriptions: HashSet<M::MessageType>,
+}
+
+impl<M> HandlerComponent<M>
+where
+    M: Message,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Associate the component with an actor that was attached to a
+    /// commutator.
+    pub fn attach(&mut self, id: ActorId, sender: &Sender<M>) {
+        self.id = Some(id);
+        self.sender = Some(sender.clone());
+    }
+
+    /// Reset the component when its actor is detached. The commutator drops
+    /// all subscriptions of a detached actor, so they are cleared as well.
+    pub fn detach(&mut self) {
+        self.id = None;
+        self.sender = None;
+        self.subscriptions.clear();
+    }
+
+    /// Get the id of the actor, if it is attached.
+    pub fn id(&self) -> Option<ActorId> {
+        self.id
+    }
+
+    /// Check if the actor is attached to a commutator.
+    pub fn is_attached(&self) -> bool {
+        self.id.is_some()
+    }
+
+    /// Get the sender of the commutator the actor is attached to.
+    pub fn sender(&self) -> Option<&Sender<M>> {
+        self.sender.as_ref()
+    }
+
+    /// Get the message types the actor is subscribed to.
+    pub fn subscriptions(&self) -> &HashSet<M::MessageType> {
+        &self.subscriptions
+    }
+
+    /// Check if the actor is subscribed to a message type.
+    pub fn is_subscribed(&self, sig: M::MessageType) -> bool {
+        self.subscriptions.contains(&sig)
+    }
+
+    /// Record a subscription that was added by the commutator.
+    pub fn insert_subscription(&mut self, sig: M::MessageType) {
+        self.subscriptions.insert(sig);
+    }
+
+    /// Record a subscription that was removed by the commutator.
+    pub fn remove_subscription(&mut self, sig: M::MessageType) {
+        self.subscriptions.remove(&sig);
+    }
+
+    /// Ask the commutator to add a subscription for the actor. The
+    /// subscriptions of the component are updated once the commutator has
+    /// added it. Returns the id of the subscription, or `None` if the actor
+    /// is not attached.
+    pub fn subscribe(&self, subscription: Subscription<M>) -> Option<SubscriptionId> {
+        self.sender.as_ref()?.subscribe(subscription)
+    }
+
+    /// Ask the commutator to remove a subscription of the actor. Returns
+    /// `false` if the actor is not attached.
+    pub fn unsubscribe(&self, subscription: Subscription<M>) -> bool {
+        match self.bound_sender() {
+            Some(sender) => {
+                sender.unsubscribe(subscription);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ask the commutator to remove a predicate subscription of the actor by
+    /// its id. Returns `false` if the actor is not attached.
+    pub fn unsubscribe_predicate(&self, subscription_id: SubscriptionId) -> bool {
+        match self.bound_sender() {
+            Some(sender) => {
+                sender.unsubscribe_predicate(subscription_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the sender of the actor, if it was handed to the actor when it
+    /// was attached.
+    fn bound_sender(&self) -> Option<&Sender<M>> {
+        self.sender
+            .as_ref()
+            .filter(|sender| sender.actor().is_some())
+    }
+
+    /// Spawn a child actor of the actor, see `Sender::spawn`. Returns `None`
+    /// if the actor is not attached, in which case the child is dropped.
+    pub fn spawn(&self, child: ActorObject<M>) -> Option<ActorRef<M>> {
+        self.sender.as_ref()?.spawn(child)
+    }
+
+    /// Create a deputy publisher that publishes in name of the actor.
+    pub fn deputy(&self) -> Option<DeputyPublisher<M>> {
+        match (self.id, &self.sender) {
+            (Some(id), Some(sender)) => Some(DeputyPublisher::new(sender.clone(), id)),
+            _ => None,
+        }
+    }
+}
+
+impl<M> Default for HandlerComponent<M>
+where
+    M: Message,
 {
     fn default() -> Self {
         Self {
-            _id: None,
+            id: None,
+            sender: None,
             subscriptions: HashSet::new(),
         }
     }
 }
 
-impl<S> Clone for HandlerComponent<S>
+/// A clone of a component is not attached, just like the clone of an actor.
+impl<M> Clone for HandlerComponent<M>
 where
-    S: MessageType,
+    M: Message,
 {
     fn clone(&self) -> Self {
-        Self {
-            _id: None,
-            subscriptions: self.subscriptions.clone(),
-        }
+        Self::default()
+    }
+}
+
+impl<M> fmt::Debug for HandlerComponent<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mut subscriptions: Vec<_> = self.subscriptions.iter().map(|sig| sig.name()).collect();
+        subscriptions.sort_unstable();
+        f.debug_struct("HandlerComponent")
+            .field("id", &self.id)
+            .field("subscriptions", &subscriptions)
+            .finish()
     }
 }
@@ -0,0 +1,175 @@
+use futures::channel::oneshot;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::actor::ActorId;
+use crate::message::*;
+use crate::Sender;
+
+pub use futures::channel::oneshot::Canceled;
+
+/// Shared flag that tracks whether an actor is still attached, and wakes the
+/// tasks that are watching the actor when it is detached.
+pub(crate) struct Liveness {
+    alive: AtomicBool,
+    watchers: Mutex<Vec<Waker>>,
+}
+
+impl Liveness {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            alive: AtomicBool::new(true),
+            watchers: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
+
+    /// Mark the actor as detached and wake all watchers.
+    pub(crate) fn terminate(&self) {
+        self.alive.store(false, Ordering::Release);
+        for waker in self.watchers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// Handle to an actor that is attached to a commutator. The handle carries
+/// the sender of the commutator, so it can be cloned and moved into tasks.
+pub struct ActorRef<M>
+where
+    M: Message,
+{
+    id: ActorId,
+    sender: Sender<M>,
+    liveness: Arc<Liveness>,
+}
+
+impl<M> ActorRef<M>
+where
+    M: Message,
+{
+    pub(crate) fn new(id: ActorId, sender: Sender<M>, liveness: Arc<Liveness>) -> Self {
+        Self {
+            id,
+            sender,
+            liveness,
+        }
+    }
+
+    /// Get the id of the actor.
+    pub fn id(&self) -> ActorId {
+        self.id
+    }
+
+    /// Check if the actor is still attached to the commutator.
+    pub fn is_alive(&self) -> bool {
+        self.liveness.is_alive()
+    }
+
+    /// Post a message to the actor. The message is given back if the actor
+    /// is no longer attached.
+    pub fn post(&self, message: M) -> Result<(), M> {
+        self.send(Envelope::new(
+            Origin::Anonymous,
+            Destination::Single(self.id),
+            message,
+        ))
+    }
+
+    /// Post a message to the actor and wait for its reply, which the actor
+    /// sends with `Envelope::reply`. Fails if the actor is no longer
+    /// attached, or if the message was dropped without a reply.
+    pub async fn ask(&self, message: M) -> Result<M, Canceled> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        let mut envelope = Envelope::new(Origin::Anonymous, Destination::Single(self.id), message);
+        envelope.reply_to = Some(ReplyTo::new(reply_sender));
+        // Dropping the envelope drops the reply sender, which cancels the
+        // receiver.
+        let _ = self.send(envelope);
+        reply_receiver.await
+    }
+
+    /// Get a future that completes when the actor is detached.
+    pub fn watch(&self) -> Watch {
+        Watch {
+            liveness: self.liveness.clone(),
+        }
+    }
+
+    fn send(&self, envelope: Envelope<M>) -> Result<(), M> {
+        if !self.is_alive() {
+            return Err(envelope.message);
+        }
+        self.sender
+            .unbounded_send(envelope)
+            .map_err(|error| error.into_inner().message)
+    }
+}
+
+impl<M> Clone for ActorRef<M>
+where
+    M: Message,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            sender: self.sender.clone(),
+            liveness: self.liveness.clone(),
+        }
+    }
+}
+
+impl<M> PartialEq for ActorRef<M>
+where
+    M: Message,
+{
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.liveness, &other.liveness)
+    }
+}
+
+impl<M> Eq for ActorRef<M> where M: Message {}
+
+impl<M> fmt::Debug for ActorRef<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ActorRef")
+            .field("id", &self.id)
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
+
+/// Future that completes when the watched actor is detached.
+pub struct Watch {
+    liveness: Arc<Liveness>,
+}
+
+impl Future for Watch {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.liveness.is_alive() {
+            return Poll::Ready(());
+        }
+        let mut watchers = self.liveness.watchers.lock().unwrap();
+        // Check again while holding the lock, so a termination in between
+        // can't be missed.
+        if !self.liveness.is_alive() {
+            return Poll::Ready(());
+        }
+        if !watchers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            watchers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::actor::{Actor, ActorId};
+use crate::message::*;
+use crate::Sender;
+
+/// Function that maps a message of one commutator to a message of another
+/// commutator. Messages that are mapped to `None` are not forwarded.
+pub type Mapping<A, B> = Box<dyn Fn(&A) -> Option<B> + Send>;
+
+/// Function that maps the replies to forwarded messages back to messages of
+/// the commutator of the bridge.
+pub type ReplyMapping<B, A> = Arc<dyn Fn(&B) -> Option<A> + Send + Sync>;
+
+/// Actor that forwards messages from the commutator it is attached to, to
+/// another commutator with a different message enum. The bridge subscribes to
+/// the given message types, maps every message it receives and publishes the
+/// result to all actors of the other commutator.
+///
+/// Bridges can be chained to forward a message across several commutators.
+/// An envelope keeps track of the commutators it was forwarded from, and is
+/// never forwarded back to any of them, so commutators can be bridged in
+/// both directions without messages bouncing between them.
+///
+/// Actor ids are only meaningful within their own commutator, so the origin
+/// of a forwarded envelope is `Origin::Bridged`, with the id of the bridge
+/// and of the actor that sent the original envelope. If the bridge maps
+/// replies with `with_replies`, an actor of the other commutator can answer a
+/// forwarded message with `Envelope::reply`. The reply is mapped back and
+/// handed to the `ActorRef::ask` of the original message, or else sent to
+/// the actor that sent it.
+pub struct Bridge<A, B>
+where
+    A: Message,
+    B: Message,
+{
+    target: Sender<B>,
+    message_types: Vec<A::MessageType>,
+    mapping: Mapping<A, B>,
+    replies: Option<ReplyMapping<B, A>>,
+    /// The sender of the commutator the bridge is attached to.
+    sender: Option<Sender<A>>,
+}
+
+impl<A, B> Bridge<A, B>
+where
+    A: Message,
+    B: Message,
+{
+    /// Create a bridge that forwards the messages of the given types to the
+    /// commutator of the `target` sender.
+    pub fn new<F>(target: &Sender<B>, message_types: Vec<A::MessageType>, mapping: F) -> Self
+    where
+        F: Fn(&A) -> Option<B> + Send + 'static,
+    {
+        Self {
+            target: target.clone(),
+            message_types,
+            mapping: Box::new(mapping),
+            replies: None,
+            sender: None,
+        }
+    }
+
+    /// Let the actors of the other commutator reply to forwarded messages,
+    /// with a mapping of their replies. Replies that are mapped to `None` are
+    /// given back to the actor that replies.
+    pub fn with_replies<F>(mut self, mapping: F) -> Self
+    where
+        F: Fn(&B) -> Option<A> + Send + Sync + 'static,
+    {
+        self.replies = Some(Arc::new(mapping));
+        self
+    }
+
+    /// Get the message types that are forwarded.
+    pub fn message_types(&self) -> &[A::MessageType] {
+        &self.message_types
+    }
+
+    /// Create the reply slot of a forwarded envelope, if replies are mapped
+    /// and the original envelope can be answered.
+    fn reply_to(
+        &self,
+        envelope: &Envelope<A>,
+        bridge: ActorId,
+        actor: Option<ActorId>,
+    ) -> Option<ReplyTo<B>> {
+        let replies = self.replies.clone()?;
+        if envelope.expects_reply() {
+            let original = envelope.reply_to.clone()?;
+            return Some(ReplyTo::with(move |reply: B| match replies(&reply) {
+                Some(message) => original.send(message).map_err(|_| reply),
+                None => Err(reply),
+            }));
+        }
+        let (actor, sender) = (actor?, self.sender.clone()?);
+        Some(ReplyTo::with(move |reply: B| match replies(&reply) {
+            Some(message) => sender
+                .unbounded_send(Envelope::new(
+                    Origin::Actor(bridge),
+                    Destination::Single(actor),
+                    message,
+                ))
+                .map_err(|_| reply),
+            None => Err(reply),
+        }))
+    }
+}
+
+impl<A, B> Actor for Bridge<A, B>
+where
+    A: Message,
+    B: Message,
+{
+    type Message = A;
+
+    fn default_subscriptions(&self) -> Vec<A::MessageType> {
+        self.message_types.clone()
+    }
+
+    fn on_attach(&mut self, sender: &Sender<A>) {
+        self.sender = Some(sender.clone());
+    }
+
+    fn on_detach(&mut self) {
+        self.sender = None;
+    }
+
+    fn handle(&mut self, envelope: &Envelope<A>) {
+        let (bridge, source) = match (self.id(), &self.sender) {
+            (Some(bridge), Some(sender)) => (bridge, sender.commutator()),
+            _ => return,
+        };
+        let target = self.target.commutator();
+        if target == source || envelope.bridged.contains(&target) {
+            return;
+        }
+        if let Some(message) = (self.mapping)(&envelope.message) {
+            let actor = match envelope.origin {
+                Origin::Actor(id) => Some(id),
+                _ => None,
+            };
+            let origin = Origin::Bridged { bridge, actor };
+            let mut forwarded = Envelope::new(origin, Destination::All, message);
+            forwarded.reply_to = self.reply_to(envelope, bridge, actor);
+            forwarded.bridged = envelope
+                .bridged
+                .iter()
+                .copied()
+                .chain(Some(source))
+                .collect();
+            if self.target.unbounded_send(forwarded).is_err() {
+                log::warn!("bridge {} can't forward, commutator is gone", bridge);
+            }
+        }
+    }
+}
+
+impl<A, B> fmt::Debug for Bridge<A, B>
+where
+    A: Message,
+    B: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let message_types: Vec<_> = self.message_types.iter().map(|sig| sig.name()).collect();
+        f.debug_struct("Bridge")
+            .field("message_types", &message_types)
+            .field("target", &self.target)
+            .finish()
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clock of a commutator, that tells the time since the commutator was
+/// created. While a journal is replayed, the clock is virtual: it tells the
+/// time at which the replayed message was received when it was recorded.
+/// While a journal is recorded, the clock likewise stands still at the time
+/// the dispatched message was received.
+///
+/// Actors that read the time from the clock, through `Sender::now`, behave
+/// the same way when a journal is replayed.
+#[derive(Debug)]
+pub struct Clock {
+    start: Instant,
+    virtual_time: Mutex<Option<Duration>>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            virtual_time: Mutex::new(None),
+        }
+    }
+
+    /// Get the time since the commutator was created, or the virtual time.
+    pub fn now(&self) -> Duration {
+        match *self.virtual_time.lock().unwrap() {
+            Some(time) => time,
+            None => self.start.elapsed(),
+        }
+    }
+
+    /// Check if the clock is virtual.
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_time.lock().unwrap().is_some()
+    }
+
+    /// Set the virtual time, or go back to the real time with `None`.
+    pub(crate) fn set_virtual(&self, time: Option<Duration>) {
+        *self.virtual_time.lock().unwrap() = time;
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
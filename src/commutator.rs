@@ -1,25 +1,85 @@
-use futures::channel::mpsc;
 use futures::stream::StreamExt;
-use std::collections::hash_map::Entry;
+use smallvec::SmallVec;
+use std::any::Any;
 use std::collections::HashMap;
-use std::collections::HashSet;
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::actor::*;
+use crate::actor_ref::{ActorRef, Liveness};
+use crate::clock::Clock;
+use crate::introspection::{ActorInfo, Topology};
+use crate::journal::{Entry, IdMap, Journal, Recorder};
 use crate::message::*;
+use crate::metrics::{ActorMetrics, Counters, Metrics};
+use crate::persistence::{Persisted, Persistent, Storage};
+use crate::registry::{Registry, RegistryError};
+use crate::sender::{Command, Control, Packet};
+use crate::store::{Key, KeyId, Store, Value};
+use crate::subscription::{Filter, Subscription, SubscriptionId, SubscriptionTable};
+use crate::system::{SystemMessage, TerminationReason};
+use crate::typed::{Typed, TypedActor};
+use crate::watchdog::{Heartbeat, Stall, Watchdog};
+
+pub use crate::sender::{Receiver, Sender};
 
-pub type Sender<M> = mpsc::UnboundedSender<Envelope<M>>;
-pub type Receiver<M> = mpsc::UnboundedReceiver<Envelope<M>>;
 pub type Interceptor<M> = fn(&mut Commutator<M>, M) -> InterceptResult<M>;
 
 /// The commutator dispatches events to the actors attached to it.
+///
+/// Dispatch order is deterministic: actors are initialized in the order in
+/// which they were attached, and a broadcast message is handed to its
+/// subscribers in the order in which they subscribed to its message type. For
+/// default subscriptions this is the attach order.
+///
+/// An actor that panics while handling a message is detached, and the actors
+/// that monitor it are notified with a `SystemMessage::Terminated`.
 pub struct Commutator<M>
 where
     M: Message,
 {
     message_sender: Sender<M>,
     message_receiver: Receiver<M>,
-    handlers: HashMap<usize, ActorObject<M>>,
-    message_map: HashMap<M::MessageType, HashSet<usize>>,
+    handlers: HashMap<ActorId, ActorObject<M>>,
+    /// The ids of the attached actors, in attach order.
+    actor_ids: Vec<ActorId>,
+    /// Liveness of the attached actors, shared with their `ActorRef`s.
+    liveness: HashMap<ActorId, Arc<Liveness>>,
+    /// The members of every group, in join order.
+    groups: HashMap<GroupId, Vec<ActorId>>,
+    /// The watchers of every monitored actor, in monitor order.
+    monitors: HashMap<ActorId, Vec<ActorId>>,
+    /// The names of the attached actors, shared with the senders.
+    registry: Arc<Registry>,
+    /// The children of every actor that spawned children, in spawn order.
+    children: HashMap<ActorId, Vec<ActorId>>,
+    /// The parent of every child actor.
+    parents: HashMap<ActorId, ActorId>,
+    /// Whether the commutator is running, so spawned children are initialized
+    /// right away.
+    running: bool,
+    subscriptions: SubscriptionTable<M::MessageType>,
+    /// The clock, shared with the senders.
+    clock: Arc<Clock>,
+    /// The state store, shared with the senders.
+    store: Arc<Store>,
+    /// The watchers of every key of the store, in watch order.
+    key_watchers: HashMap<KeyId, Vec<ActorId>>,
+    /// The recorder of the journal, while recording.
+    recorder: Option<Box<dyn Recorder<M>>>,
+    /// The mapping of the recorded actor ids, while replaying a journal.
+    replay_ids: Option<IdMap>,
+    /// The counters of the metrics.
+    counters: Counters,
+    /// How long an actor may take to handle a message, unless it has its
+    /// own budget.
+    handle_budget: Option<Duration>,
+    /// The budgets of the actors that have their own budget.
+    actor_budgets: HashMap<ActorId, Duration>,
+    /// The heartbeat of the loop, shared with the watchdogs.
+    heartbeat: Arc<Heartbeat>,
 
     /// The interceptor closure is called after the message is received and
     /// before it is passed to the attached handlers.
@@ -31,16 +91,34 @@ where
     M: Message,
 {
     pub fn new() -> Commutator<M> {
-        let (message_sender, message_receiver) = mpsc::unbounded::<Envelope<M>>();
-        // Every event is a key in the hashmap, the value is a hashset of
-        // all the event handlers that are subscribed to that event.
-        let event_map: HashMap<M::MessageType, HashSet<usize>> = HashMap::new();
+        let registry = Arc::new(Registry::default());
+        let clock = Arc::new(Clock::new());
+        let store = Arc::new(Store::default());
+        let (message_sender, message_receiver) =
+            Sender::channel(registry.clone(), clock.clone(), store.clone());
         Commutator {
             message_sender,
             message_receiver,
             interceptor: |_, message| InterceptResult::Pass(message),
             handlers: HashMap::new(),
-            message_map: event_map,
+            actor_ids: Vec::new(),
+            liveness: HashMap::new(),
+            groups: HashMap::new(),
+            monitors: HashMap::new(),
+            registry,
+            children: HashMap::new(),
+            parents: HashMap::new(),
+            running: false,
+            subscriptions: SubscriptionTable::new(),
+            clock,
+            store,
+            key_watchers: HashMap::new(),
+            recorder: None,
+            replay_ids: None,
+            counters: Counters::new::<M>(),
+            handle_budget: None,
+            actor_budgets: HashMap::new(),
+            heartbeat: Arc::new(Heartbeat::new()),
         }
     }
 
@@ -48,160 +126,961 @@ where
     /// dispatched to the attached actors.
     pub async fn run(&mut self) {
         self.init();
-        'main: loop {
-            let envelope = self.message_receiver.next().await;
-            if let Some(envelope) = envelope {
-                let Envelope {
-                    origin,
-                    destination,
-                    message,
-                } = envelope;
-
-                let message = match (self.interceptor)(self, message) {
-                    InterceptResult::Pass(message) => message,
-                    InterceptResult::Interception => continue 'main,
-                    InterceptResult::Break => break 'main,
-                };
-
-                // There are few special events that are handled by the
+        self.running = true;
+        while let Some(packet) = self.message_receiver.next().await {
+            self.message_sender.dequeued();
+            self.heartbeat.busy();
+            match packet {
+                // Envelopes are dispatched to the attached event handlers
+                // according to the destination defined in the envelope.
+                Packet::Envelope(envelope) => {
+                    let recording = self.recorder.is_some();
+                    let envelope = self.record_received(envelope);
+                    let running = self.receive(envelope);
+                    if recording {
+                        self.clock.set_virtual(None);
+                    }
+                    if !running {
+                        self.heartbeat.idle();
+                        break;
+                    }
+                }
+                // There are few special packets that are handled by the
                 // commutator itself instead of being dispatched to the
-                // attached event handlers, namely the `Attach` and `Detach`
-                // events that are used for attaching and detaching actors
-                // dynamically.
-                match message {
-                    // All other events are dispatched to the attached event
-                    // handlers according to the destination defined in the
-                    // event envelope.
-                    message => match destination {
-                        Destination::Single(id) => {
-                            if let Some(handler) = self.get_handler(id) {
-                                let envelope = Envelope {
-                                    origin,
-                                    message,
-                                    destination,
-                                };
-                                handler.handle(&envelope);
-                            }
-                        }
-                        Destination::All => {
-                            let envelope = Envelope {
-                                origin,
-                                message,
-                                destination,
-                            };
-                            self.dispatch(&envelope);
-                        }
-                    },
+                // attached event handlers, namely the control commands that
+                // are used for spawning and monitoring actors dynamically.
+                Packet::Control(Control(command)) => self.execute(command),
+            }
+            self.heartbeat.idle();
+        }
+        self.running = false;
+        self.shut_down();
+    }
+
+    /// Intercept a received envelope and dispatch it. Returns `false` if the
+    /// interceptor breaks the loop.
+    fn receive(&mut self, envelope: Envelope<M>) -> bool {
+        let Envelope {
+            origin,
+            destination,
+            message,
+            reply_to,
+            span,
+            bridged,
+        } = envelope;
+
+        self.counters.received += 1;
+        let message = match (self.interceptor)(self, message) {
+            InterceptResult::Pass(message) => message,
+            InterceptResult::Interception => {
+                self.counters.dropped += 1;
+                return true;
+            }
+            InterceptResult::Break => {
+                self.counters.dropped += 1;
+                return false;
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            parent: span.0.as_ref().unwrap_or(&tracing::Span::current()),
+            "dispatch",
+            message_type = M::MessageType::from(&message).name(),
+            origin = ?origin,
+            destination = ?destination,
+            handlers = tracing::field::Empty,
+        )
+        .entered();
+
+        let envelope = Envelope {
+            origin,
+            destination,
+            message,
+            reply_to,
+            span,
+            bridged,
+        };
+        self.dispatch(&envelope);
+        true
+    }
+
+    /// Record a received envelope, if the commutator is recording. The clock
+    /// is stopped at the time the envelope was received, so the actors see
+    /// the same time while it is dispatched as when it is replayed.
+    fn record_received(&mut self, envelope: Envelope<M>) -> Envelope<M> {
+        let recorder = match self.recorder.as_mut() {
+            Some(recorder) => recorder,
+            None => return envelope,
+        };
+        let time = self.clock.now();
+        self.clock.set_virtual(Some(time));
+        let entry = Entry::Received { time, envelope };
+        recorder.record(&entry);
+        match entry {
+            Entry::Received { envelope, .. } => envelope,
+            Entry::Attached { .. } => unreachable!(),
+        }
+    }
+
+    /// Start recording a journal of the envelopes the commutator receives
+    /// while it runs, replacing the current recorder. The actors that are
+    /// attached are recorded first.
+    pub fn record(&mut self, recorder: impl Recorder<M> + 'static) {
+        let mut recorder: Box<dyn Recorder<M>> = Box::new(recorder);
+        let time = self.clock.now();
+        for id in self.actor_ids.iter().copied() {
+            recorder.record(&Entry::Attached { time, id });
+        }
+        self.recorder = Some(recorder);
+    }
+
+    /// Stop recording, and get back the recorder.
+    pub fn stop_recording(&mut self) -> Option<Box<dyn Recorder<M>>> {
+        self.recorder.take()
+    }
+
+    /// Replay a journal that was recorded by a commutator with the same
+    /// actors, attached in the same order. The actors are initialized, and
+    /// the recorded envelopes are intercepted and dispatched in order, while
+    /// the clock tells the time at which they were recorded. The ids of the
+    /// recorded actors are replaced by the ids of the corresponding actors.
+    ///
+    /// The envelopes the actors send while the journal is replayed are
+    /// dropped, since the journal already contains them. Control commands,
+    /// such as spawning a child, are executed. The clock is real again
+    /// after the replay.
+    pub fn replay(&mut self, journal: Journal<M>) {
+        self.init();
+        self.running = true;
+        self.replay_ids = Some(IdMap::new(self.actor_ids.clone()));
+        for entry in journal.into_entries() {
+            self.discard_pending();
+            match entry {
+                Entry::Attached { id, .. } => {
+                    if let Some(ids) = self.replay_ids.as_mut() {
+                        ids.recorded(id);
+                    }
+                }
+                Entry::Received { time, envelope } => {
+                    self.clock.set_virtual(Some(time));
+                    let envelope = match &self.replay_ids {
+                        Some(ids) => ids.translate_envelope(envelope),
+                        None => envelope,
+                    };
+                    if !self.receive(envelope) {
+                        break;
+                    }
                 }
-            } else {
-                break 'main;
             }
         }
+        self.discard_pending();
+        self.replay_ids = None;
+        self.clock.set_virtual(None);
+        self.running = false;
+    }
+
+    /// Execute the pending control commands and drop the pending envelopes.
+    fn discard_pending(&mut self) {
+        while let Ok(packet) = self.message_receiver.try_recv() {
+            self.message_sender.dequeued();
+            match packet {
+                Packet::Envelope(_) => self.counters.dropped += 1,
+                Packet::Control(Control(command)) => self.execute(command),
+            }
+        }
+    }
+
+    /// Get the clock of the commutator.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
     }
 
     fn init(&mut self) {
-        for handler in self.handlers.values_mut() {
-            handler.init();
+        for id in self.actor_ids.clone() {
+            self.guard(id, |actor| actor.init());
         }
     }
 
-    /// Dispatch an event to all the attached event handlers who are
-    /// subscribed to the given event.
-    fn dispatch(&mut self, envelope: &Envelope<M>) {
-        let handlers = match self
-            .message_map
-            .get(&M::MessageType::from(&envelope.message))
-        {
-            Some(handlers) => handlers,
+    /// Execute a control command that was sent to the commutator.
+    fn execute(&mut self, command: Command<M>) {
+        match command {
+            Command::Monitor { watcher, target } => {
+                self.monitor(watcher, target);
+            }
+            Command::Demonitor { watcher, target } => {
+                self.demonitor(watcher, target);
+            }
+            Command::Spawn {
+                parent,
+                id,
+                child,
+                liveness,
+            } => {
+                self.attach_child_as(parent, id, liveness, child);
+            }
+            Command::DetachChild { parent, child } => {
+                if self.parents.get(&child) == Some(&parent) {
+                    self.guard(child, |actor| actor.deinit());
+                    self.detach(child);
+                }
+            }
+            Command::Subscribe {
+                id,
+                subscription_id,
+                subscription,
+            } => {
+                self.subscribe_as(id, subscription_id, subscription);
+            }
+            Command::Unsubscribe { id, subscription } => {
+                self.unsubscribe(id, subscription);
+            }
+            Command::UnsubscribePredicate {
+                id,
+                subscription_id,
+            } => {
+                self.unsubscribe_predicate(id, subscription_id);
+            }
+            Command::SetFilter { id, sig, filter } => {
+                self.set_filter(id, sig, filter);
+            }
+            Command::ClearFilter { id, sig } => {
+                self.clear_filter(id, sig);
+            }
+            Command::Store { key, value } => self.write_value(key, value),
+            Command::Watch { id, key } => {
+                self.watch(id, key);
+            }
+            Command::Unwatch { id, key } => {
+                remove_from_list(&mut self.key_watchers, &key, id);
+            }
+        }
+    }
+
+    /// Call an attached actor, and detach it if it panics.
+    fn guard(&mut self, id: ActorId, call: impl FnOnce(&mut dyn Actor<Message = M>)) {
+        let actor = match self.handlers.get_mut(&id) {
+            Some(actor) => actor,
             None => return,
         };
-
-        for handler_id in handlers.into_iter() {
-            self.handlers.get_mut(handler_id).unwrap().handle(&envelope);
+        let called = Called::new(id, actor.as_ref());
+        let call = || as_actor(called, || call(actor.as_mut()));
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(call)) {
+            let message = match panic.downcast::<String>() {
+                Ok(message) => *message,
+                Err(panic) => match panic.downcast::<&'static str>() {
+                    Ok(message) => message.to_string(),
+                    Err(_) => String::from("unknown panic"),
+                },
+            };
+            log::error!("actor {} panicked: {}", id, message);
+            self.detach_with_reason(id, TerminationReason::Panicked(message));
         }
     }
 
-    fn custom_attach(&mut self, mut actor: Box<dyn Actor<Message = M>>, init: bool) -> usize {
-        // The id of an event handler is the memory address of the Box that
-        // contains it.
-        let id = actor.id();
-        actor.on_attach(&self.message_sender);
-        let default_subscriptions = actor.default_subscriptions();
-        for sig in default_subscriptions {
-            match self.message_map.entry(sig) {
-                Entry::Occupied(mut entry) => {
-                    entry.get_mut().insert(id);
-                }
-                Entry::Vacant(entry) => {
-                    let mut subscribers = HashSet::new();
-                    subscribers.insert(id);
-                    entry.insert(subscribers);
+    /// Dispatch an event to the attached event handlers according to the
+    /// destination defined in the event envelope. Broadcast events are handed
+    /// to the subscribers in subscription order, group events to the members
+    /// in join order.
+    fn dispatch(&mut self, envelope: &Envelope<M>) {
+        let targets: SmallVec<[ActorId; 4]> = match &envelope.destination {
+            Destination::All => self.subscriptions.route(&envelope.message),
+            Destination::AllExcept(except) => {
+                let mut targets = self.subscriptions.route(&envelope.message);
+                targets.retain(|id| id != except);
+                targets
+            }
+            Destination::Single(id) => SmallVec::from_elem(*id, 1),
+            Destination::Many(ids) => {
+                let mut targets = SmallVec::new();
+                for id in ids {
+                    if !targets.contains(id) {
+                        targets.push(*id);
+                    }
                 }
+                targets
+            }
+            Destination::Group(group) => SmallVec::from(self.group_members(group)),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("handlers", tracing::field::debug(&targets));
+        self.counters.dispatched[M::MessageType::from(&envelope.message).index()] += 1;
+        let mut delivered = false;
+        for id in targets {
+            if !self.handlers.contains_key(&id) {
+                continue;
+            }
+            delivered = true;
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("handle", actor = %id).entered();
+            self.heartbeat.calling(Some(id));
+            let start = Instant::now();
+            self.guard(id, |actor| actor.handle(envelope));
+            let elapsed = start.elapsed();
+            self.heartbeat.calling(None);
+            // The actor is detached if it panicked.
+            if self.handlers.contains_key(&id) {
+                let over_budget = self.check_budget(id, envelope, elapsed);
+                self.counters.handled(id, elapsed, over_budget);
             }
-            self.message_map.get_mut(&sig).unwrap().insert(id);
-            actor.insert_subscription(sig);
         }
-        if init {
-            actor.init()
+        if !delivered {
+            self.counters.dead_letters += 1;
+        }
+    }
+
+    /// Check if an actor took longer to handle a message than its budget, and
+    /// log it if it did.
+    fn check_budget(&self, id: ActorId, envelope: &Envelope<M>, elapsed: Duration) -> bool {
+        let budget = match self.actor_budgets.get(&id).copied().or(self.handle_budget) {
+            Some(budget) => budget,
+            None => return false,
+        };
+        if elapsed <= budget {
+            return false;
+        }
+        log::warn!(
+            "actor {} took {:?} to handle {}, over its budget of {:?}",
+            id,
+            elapsed,
+            M::MessageType::from(&envelope.message).name(),
+            budget
+        );
+        true
+    }
+
+    fn custom_attach(
+        &mut self,
+        id: ActorId,
+        liveness: Arc<Liveness>,
+        mut actor: Box<dyn Actor<Message = M>>,
+        init: bool,
+    ) -> ActorRef<M> {
+        let called = Called::new(id, actor.as_ref());
+        let (filters, groups) = as_actor(called, || {
+            actor.on_attach(&self.message_sender.for_actor(id));
+            for subscription in actor.initial_subscriptions() {
+                Self::add_subscription(
+                    &mut self.subscriptions,
+                    actor.as_mut(),
+                    id,
+                    SubscriptionId::next(),
+                    subscription,
+                );
+            }
+            (actor.subscription_filters(), actor.default_groups())
+        });
+        for (sig, filter) in filters {
+            self.subscriptions.set_filter(sig, id, filter);
+        }
+        for group in groups {
+            self.join_group(id, group);
         }
         self.handlers.insert(id, actor);
-        return id;
+        self.actor_ids.push(id);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&Entry::Attached {
+                time: self.clock.now(),
+                id,
+            });
+        }
+        if let Some(ids) = self.replay_ids.as_mut() {
+            ids.attached(id);
+        }
+        self.liveness.insert(id, liveness.clone());
+        if init {
+            self.guard(id, |actor| actor.init());
+        }
+        ActorRef::new(id, self.message_sender.clone(), liveness)
     }
 
-    /// Attach an event handler to the commutator.
-    pub fn attach(&mut self, actor: Box<dyn Actor<Message = M>>) -> usize {
-        self.custom_attach(actor, false)
+    fn add_subscription(
+        subscriptions: &mut SubscriptionTable<M::MessageType>,
+        actor: &mut dyn Actor<Message = M>,
+        id: ActorId,
+        subscription_id: SubscriptionId,
+        subscription: Subscription<M>,
+    ) {
+        if let Subscription::One(sig) = subscription {
+            if sig.is_skipped() {
+                log::warn!("actor {} can't subscribe to skipped {}", id, sig.name());
+            }
+        }
+        for sig in subscriptions.subscribe_as(subscription_id, subscription, id) {
+            as_actor(Called::new(id, &*actor), || actor.insert_subscription(sig));
+        }
+    }
+
+    /// Attach an event handler to the commutator. The event handler gets a
+    /// new id, which is the id of the returned handle.
+    pub fn attach(&mut self, actor: Box<dyn Actor<Message = M>>) -> ActorRef<M> {
+        let id = self.message_sender.next_id();
+        self.custom_attach(id, Liveness::new(), actor, false)
     }
 
     /// Attach an event handler to the commutator and initialize it.
-    pub fn attach_and_init(&mut self, actor: Box<dyn Actor<Message = M>>) -> usize {
-        self.custom_attach(actor, true)
+    pub fn attach_and_init(&mut self, actor: Box<dyn Actor<Message = M>>) -> ActorRef<M> {
+        let id = self.message_sender.next_id();
+        self.custom_attach(id, Liveness::new(), actor, true)
+    }
+
+    /// Attach a persistent actor to the commutator. The actor is rebuilt from
+    /// the storage before it is attached, and the messages it handles are
+    /// persisted in the storage.
+    pub fn attach_persistent<A, S>(&mut self, actor: A, storage: S) -> ActorRef<M>
+    where
+        A: Persistent<Message = M>,
+        S: Storage<M, A::Snapshot> + 'static,
+    {
+        self.attach(Box::new(Persisted::new(actor, storage)))
+    }
+
+    /// Attach a typed actor to the commutator. The messages that are
+    /// dispatched to it are converted into its local message type.
+    pub fn attach_typed<A>(&mut self, actor: A) -> ActorRef<M>
+    where
+        A: TypedActor<Message = M>,
+    {
+        self.attach(Box::new(Typed::new(actor)))
     }
 
-    /// Detach an event handler from the commutator.
-    pub fn detach(&mut self, id: usize) -> Option<Box<dyn Actor<Message = M>>> {
-        // Remove all the references to the handler in the event map
-        for handler_ids in self.message_map.values_mut() {
-            handler_ids.remove(&id);
+    /// Attach a child actor of an attached `parent` actor. The child is
+    /// initialized right away if the commutator is running, and is detached
+    /// automatically when the parent is detached. Returns `None` if the parent
+    /// isn't attached, in which case the child is dropped.
+    pub fn attach_child(
+        &mut self,
+        parent: ActorId,
+        child: Box<dyn Actor<Message = M>>,
+    ) -> Option<ActorRef<M>> {
+        let id = self.message_sender.next_id();
+        self.attach_child_as(parent, id, Liveness::new(), child)
+    }
+
+    /// Attach a child actor with an id and a liveness that were handed out
+    /// when it was spawned. The liveness is terminated if the child is
+    /// dropped.
+    fn attach_child_as(
+        &mut self,
+        parent: ActorId,
+        id: ActorId,
+        liveness: Arc<Liveness>,
+        child: Box<dyn Actor<Message = M>>,
+    ) -> Option<ActorRef<M>> {
+        if !self.handlers.contains_key(&parent) {
+            log::warn!("parent actor {} is gone, dropping child", parent);
+            liveness.terminate();
+            return None;
         }
-        if let Some(mut handler) = self.handlers.remove(&id) {
-            handler.on_detach();
-            return Some(handler);
-        } else {
+        self.children.entry(parent).or_default().push(id);
+        self.parents.insert(id, parent);
+        Some(self.custom_attach(id, liveness, child, self.running))
+    }
+
+    /// Get the children of an actor, in spawn order.
+    pub fn children(&self, id: ActorId) -> &[ActorId] {
+        self.children
+            .get(&id)
+            .map_or(&[], |children| children.as_slice())
+    }
+
+    /// Get the parent of an actor, if it was spawned as a child.
+    pub fn parent(&self, id: ActorId) -> Option<ActorId> {
+        self.parents.get(&id).copied()
+    }
+
+    /// Attach an event handler to the commutator under a name, so other actors
+    /// can look it up with `resolve`. The actor isn't attached if the name is
+    /// already taken.
+    pub fn attach_named(
+        &mut self,
+        name: impl Into<String>,
+        actor: Box<dyn Actor<Message = M>>,
+    ) -> Result<ActorRef<M>, RegistryError> {
+        let name = name.into();
+        if let Some(id) = self.registry.resolve(&name) {
+            return Err(RegistryError::NameTaken { name, id });
+        }
+        let actor_ref = self.attach(actor);
+        self.registry.register(name, actor_ref.id())?;
+        Ok(actor_ref)
+    }
+
+    /// Register an attached actor under an additional name. The name is
+    /// released when the actor is detached.
+    pub fn register(&mut self, name: impl Into<String>, id: ActorId) -> Result<(), RegistryError> {
+        if !self.handlers.contains_key(&id) {
+            return Err(RegistryError::NotAttached(id));
+        }
+        self.registry.register(name.into(), id)
+    }
+
+    /// Release a name. Returns the id of the actor that was registered under
+    /// the name.
+    pub fn unregister(&mut self, name: &str) -> Option<ActorId> {
+        self.registry.unregister(name)
+    }
+
+    /// Get the id of the actor that is registered under the given name.
+    pub fn resolve(&self, name: &str) -> Option<ActorId> {
+        self.registry.resolve(name)
+    }
+
+    /// Get the registry of the commutator.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Detach an event handler from the commutator. The actors that monitor
+    /// it are notified. Its children are deinitialized and detached first.
+    pub fn detach(&mut self, id: ActorId) -> Option<Box<dyn Actor<Message = M>>> {
+        self.detach_with_reason(id, TerminationReason::Detached)
+    }
+
+    /// Detach an event handler and downcast it to its concrete type. If the
+    /// handler isn't of type `T`, it stays attached and `None` is returned.
+    pub fn detach_as<T>(&mut self, id: ActorId) -> Option<Box<T>>
+    where
+        T: Actor<Message = M>,
+    {
+        let actor: &dyn Any = self.handlers.get(&id)?.as_ref();
+        if !actor.is::<T>() {
             return None;
         }
+        let actor: Box<dyn Any> = self.detach(id)?;
+        actor.downcast().ok()
+    }
+
+    fn detach_with_reason(
+        &mut self,
+        id: ActorId,
+        reason: TerminationReason,
+    ) -> Option<Box<dyn Actor<Message = M>>> {
+        for child in self.children.remove(&id).unwrap_or_default() {
+            self.guard(child, |actor| actor.deinit());
+            self.detach_with_reason(child, TerminationReason::Detached);
+        }
+        if let Some(parent) = self.parents.remove(&id) {
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.retain(|sibling| *sibling != id);
+            }
+        }
+        // Remove all the references to the handler in the subscription table
+        self.subscriptions.remove_actor(id);
+        self.actor_ids.retain(|actor_id| *actor_id != id);
+        remove_from_lists(&mut self.groups, id);
+        if let Some(liveness) = self.liveness.remove(&id) {
+            liveness.terminate();
+        }
+        remove_from_lists(&mut self.monitors, id);
+        remove_from_lists(&mut self.key_watchers, id);
+        self.registry.remove_actor(id);
+        self.counters.actors.remove(&id);
+        self.actor_budgets.remove(&id);
+        let handler = self.handlers.remove(&id).map(|mut handler| {
+            as_actor(Called::new(id, handler.as_ref()), || handler.on_detach());
+            handler
+        });
+        if handler.is_some() {
+            self.notify_watchers(id, reason);
+        }
+        handler
+    }
+
+    /// Let the `watcher` actor be notified with a `SystemMessage::Terminated`
+    /// when the `target` actor is detached, panics or when the commutator
+    /// shuts down, which is when `run` stops or when the commutator is
+    /// dropped. If the target isn't attached, the watcher is notified right
+    /// away. Returns `false` if the watcher isn't attached.
+    pub fn monitor(&mut self, watcher: ActorId, target: ActorId) -> bool {
+        if !self.handlers.contains_key(&watcher) {
+            return false;
+        }
+        if self.handlers.contains_key(&target) {
+            add_to_list(&mut self.monitors, target, watcher);
+        } else {
+            self.notify(watcher, target, TerminationReason::NotAttached);
+        }
+        true
+    }
+
+    /// Stop monitoring the `target` actor. Returns `false` if the watcher
+    /// wasn't monitoring the target.
+    pub fn demonitor(&mut self, watcher: ActorId, target: ActorId) -> bool {
+        remove_from_list(&mut self.monitors, &target, watcher)
+    }
+
+    /// Notify all watchers that the commutator shut down, in the attach order
+    /// of their targets, and remove the monitors.
+    fn shut_down(&mut self) {
+        let mut targets: Vec<ActorId> = self.monitors.keys().copied().collect();
+        targets.sort_by_key(|target| self.actor_ids.iter().position(|id| id == target));
+        for target in targets {
+            self.notify_watchers(target, TerminationReason::Shutdown);
+        }
+    }
+
+    fn notify_watchers(&mut self, target: ActorId, reason: TerminationReason) {
+        for watcher in self.monitors.remove(&target).unwrap_or_default() {
+            self.notify(watcher, target, reason.clone());
+        }
+    }
+
+    fn notify(&mut self, watcher: ActorId, target: ActorId, reason: TerminationReason) {
+        let envelope = Envelope::new(
+            Origin::System,
+            Destination::Single(watcher),
+            SystemMessage::Terminated { id: target, reason },
+        );
+        self.guard(watcher, |actor| actor.handle_system(&envelope));
+    }
+
+    /// Get the state store of the commutator.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Write a value to the store, and notify the actors that watch the key.
+    pub fn store_value<T>(&mut self, key: &Key<T>, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.write_value(key.id(), Some(Box::new(value)));
+    }
+
+    /// Remove a value from the store, and notify the actors that watch the
+    /// key if there was a value.
+    pub fn remove_value<T>(&mut self, key: &Key<T>)
+    where
+        T: 'static,
+    {
+        self.write_value(key.id(), None);
+    }
+
+    fn write_value(&mut self, key: KeyId, value: Option<Value>) {
+        match value {
+            Some(value) => self.store.set(key, value),
+            None => {
+                if !self.store.remove(&key) {
+                    return;
+                }
+            }
+        }
+        let watchers = self.key_watchers.get(&key).cloned().unwrap_or_default();
+        let envelope = Envelope::new(
+            Origin::System,
+            Destination::Many(watchers.clone()),
+            SystemMessage::Changed {
+                key: key.name.to_string(),
+            },
+        );
+        for watcher in watchers {
+            self.guard(watcher, |actor| actor.handle_system(&envelope));
+        }
+    }
+
+    /// Let the `watcher` actor be notified with a `SystemMessage::Changed`
+    /// when the value of a key changes. Returns `false` if the watcher isn't
+    /// attached.
+    pub fn watch_key<T>(&mut self, watcher: ActorId, key: &Key<T>) -> bool
+    where
+        T: 'static,
+    {
+        self.watch(watcher, key.id())
+    }
+
+    fn watch(&mut self, watcher: ActorId, key: KeyId) -> bool {
+        if !self.handlers.contains_key(&watcher) {
+            return false;
+        }
+        add_to_list(&mut self.key_watchers, key, watcher);
+        true
+    }
+
+    /// Stop notifying the `watcher` actor about changes of a key. Returns
+    /// `false` if the watcher wasn't watching the key.
+    pub fn unwatch_key<T>(&mut self, watcher: ActorId, key: &Key<T>) -> bool
+    where
+        T: 'static,
+    {
+        remove_from_list(&mut self.key_watchers, &key.id(), watcher)
+    }
+
+    /// Add a subscription for an attached actor. Returns the id of the
+    /// subscription, or `None` if no actor with the given id is attached.
+    pub fn subscribe(
+        &mut self,
+        id: ActorId,
+        subscription: Subscription<M>,
+    ) -> Option<SubscriptionId> {
+        let subscription_id = SubscriptionId::next();
+        if self.subscribe_as(id, subscription_id, subscription) {
+            Some(subscription_id)
+        } else {
+            None
+        }
+    }
+
+    fn subscribe_as(
+        &mut self,
+        id: ActorId,
+        subscription_id: SubscriptionId,
+        subscription: Subscription<M>,
+    ) -> bool {
+        match self.handlers.get_mut(&id) {
+            Some(actor) => {
+                Self::add_subscription(
+                    &mut self.subscriptions,
+                    actor.as_mut(),
+                    id,
+                    subscription_id,
+                    subscription,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a subscription of an attached actor. Returns `false` if no
+    /// actor with the given id is attached. Predicate subscriptions are
+    /// removed with `unsubscribe_predicate`.
+    pub fn unsubscribe(&mut self, id: ActorId, subscription: Subscription<M>) -> bool {
+        match self.handlers.get_mut(&id) {
+            Some(actor) => {
+                for sig in self.subscriptions.unsubscribe(subscription, id) {
+                    as_actor(Called::new(id, actor.as_ref()), || {
+                        actor.remove_subscription(sig)
+                    });
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a predicate subscription of an actor by its id. Returns `false`
+    /// if the actor has no predicate subscription with that id.
+    pub fn unsubscribe_predicate(&mut self, id: ActorId, subscription_id: SubscriptionId) -> bool {
+        self.subscriptions
+            .unsubscribe_predicate(subscription_id, id)
+    }
+
+    /// Set the filter of an attached actor for a message type. Messages of
+    /// that type are only dispatched to the actor if the filter returns
+    /// `true`. Returns `false` if no actor with the given id is attached.
+    pub fn set_filter(&mut self, id: ActorId, sig: M::MessageType, filter: Filter<M>) -> bool {
+        if self.handlers.contains_key(&id) {
+            self.subscriptions.set_filter(sig, id, filter);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove the filter of an actor for a message type. Returns `false` if
+    /// the actor had no filter for the message type.
+    pub fn clear_filter(&mut self, id: ActorId, sig: M::MessageType) -> bool {
+        self.subscriptions.clear_filter(sig, id)
+    }
+
+    /// Let an attached actor join a group. Returns `false` if no actor with
+    /// the given id is attached or if it already is a member of the group.
+    pub fn join(&mut self, id: ActorId, group: impl Into<GroupId>) -> bool {
+        self.handlers.contains_key(&id) && self.join_group(id, group.into())
+    }
+
+    fn join_group(&mut self, id: ActorId, group: GroupId) -> bool {
+        add_to_list(&mut self.groups, group, id)
+    }
+
+    /// Let an actor leave a group. Returns `false` if the actor wasn't a
+    /// member of the group.
+    pub fn leave(&mut self, id: ActorId, group: impl Into<GroupId>) -> bool {
+        remove_from_list(&mut self.groups, &group.into(), id)
+    }
+
+    /// Get the members of a group, in join order.
+    pub fn group_members(&self, group: &GroupId) -> &[ActorId] {
+        self.groups
+            .get(group)
+            .map_or(&[], |members| members.as_slice())
+    }
+
+    /// Get a handle to an attached actor.
+    pub fn actor_ref(&self, id: ActorId) -> Option<ActorRef<M>> {
+        self.liveness
+            .get(&id)
+            .map(|liveness| ActorRef::new(id, self.message_sender.clone(), liveness.clone()))
     }
 
     /// Get a mutable reference to an event handler.
-    pub fn get_handler(&mut self, key: usize) -> Option<&mut Box<dyn Actor<Message = M>>> {
+    pub fn get_handler(&mut self, key: ActorId) -> Option<&mut Box<dyn Actor<Message = M>>> {
         self.handlers.get_mut(&key)
     }
 
-    pub fn handlers(&self) -> &HashMap<usize, Box<dyn Actor<Message = M>>> {
+    /// Get an attached actor as its concrete type. Returns `None` if no actor
+    /// with the given id is attached or if it isn't of type `T`.
+    pub fn get_actor<T>(&mut self, id: ActorId) -> Option<&mut T>
+    where
+        T: Actor<Message = M>,
+    {
+        let actor: &mut dyn Any = self.handlers.get_mut(&id)?.as_mut();
+        actor.downcast_mut()
+    }
+
+    pub fn handlers(&self) -> &HashMap<ActorId, Box<dyn Actor<Message = M>>> {
         &self.handlers
     }
 
+    /// Get the ids of the attached actors, in attach order.
+    pub fn actor_ids(&self) -> &[ActorId] {
+        &self.actor_ids
+    }
+
+    /// Get the subscription table of the commutator.
+    pub fn subscriptions(&self) -> &SubscriptionTable<M::MessageType> {
+        &self.subscriptions
+    }
+
+    /// Get the sender of the commutator, which can be cloned and used to
+    /// send events to the commutator.
+    pub fn sender(&self) -> &Sender<M> {
+        &self.message_sender
+    }
+
     /// Publish an event to all handlers.
     pub fn publish(&mut self, event: M) {
-        let envelope = Envelope {
-            origin: Origin::Anonymous,
-            destination: Destination::All,
-            message: event,
-        };
+        let envelope = Envelope::new(Origin::Anonymous, Destination::All, event);
         self.message_sender.unbounded_send(envelope).unwrap();
     }
 
-    /// Drain all the events that are currently in the receiver.
+    /// Drain all the events that are currently in the receiver. Control
+    /// commands in the receiver are executed.
     pub fn drain(&mut self) -> Vec<Envelope<M>> {
         let mut events = Vec::new();
-        loop {
-            match self.message_receiver.try_next() {
-                Ok(Some(event)) => events.push(event),
-                Ok(None) => break,
-                Err(_) => break,
+        while let Ok(packet) = self.message_receiver.try_recv() {
+            self.message_sender.dequeued();
+            match packet {
+                Packet::Envelope(event) => events.push(event),
+                Packet::Control(Control(command)) => self.execute(command),
             }
         }
-        return events;
+        events
+    }
+
+    /// Get a snapshot of the attached actors, their subscriptions and the
+    /// queue of the commutator.
+    pub fn introspect(&self) -> Topology {
+        let actors = self
+            .actor_ids
+            .iter()
+            .map(|id| {
+                let actor = &self.handlers[id];
+                let groups = self
+                    .groups
+                    .iter()
+                    .filter(|(_, members)| members.contains(id))
+                    .map(|(group, _)| group.clone());
+                let mut groups: Vec<GroupId> = groups.collect();
+                groups.sort();
+                ActorInfo {
+                    id: *id,
+                    names: self.registry.names_of(*id),
+                    type_name: actor.type_name(),
+                    subscriptions: self
+                        .subscriptions
+                        .subscriptions(*id)
+                        .iter()
+                        .map(|sig| sig.name())
+                        .collect(),
+                    predicates: self.subscriptions.predicate_count(*id),
+                    groups,
+                    parent: self.parents.get(id).copied(),
+                    handled: self
+                        .counters
+                        .actors
+                        .get(id)
+                        .map_or(0, |actor| actor.handled),
+                }
+            })
+            .collect();
+        Topology {
+            message_types: M::MessageType::ALL.iter().map(|sig| sig.name()).collect(),
+            actors,
+            queue_length: self.message_sender.queue_length(),
+            received: self.counters.received,
+        }
+    }
+
+    /// Get a snapshot of the metrics of the commutator.
+    pub fn metrics(&self) -> Metrics {
+        let counters = &self.counters;
+        let dispatched = M::MessageType::ALL
+            .iter()
+            .map(|sig| (sig.name(), counters.dispatched[sig.index()]))
+            .collect();
+        let actors = self
+            .actor_ids
+            .iter()
+            .map(|id| {
+                let actor = counters.actors.get(id).cloned().unwrap_or_default();
+                ActorMetrics {
+                    id: *id,
+                    name: self.registry.names_of(*id).into_iter().next(),
+                    handled: actor.handled,
+                    over_budget: actor.over_budget,
+                    handling_time: actor.handling_time,
+                }
+            })
+            .collect();
+        Metrics {
+            queue_length: self.message_sender.queue_length(),
+            received: counters.received,
+            dispatched,
+            dropped: counters.dropped,
+            dead_letters: counters.dead_letters,
+            actors,
+        }
+    }
+
+    /// Set how long an actor may take to handle a message, unless it has its
+    /// own budget. Actors that take longer are logged, and counted in the
+    /// metrics. `None` removes the budget.
+    pub fn set_handle_budget(&mut self, budget: Option<Duration>) {
+        self.handle_budget = budget;
+    }
+
+    /// Set the budget of an attached actor, or let it use the budget of the
+    /// commutator again with `None`. Returns `false` if no actor with the
+    /// given id is attached.
+    pub fn set_actor_budget(&mut self, id: ActorId, budget: Option<Duration>) -> bool {
+        if !self.handlers.contains_key(&id) {
+            return false;
+        }
+        match budget {
+            Some(budget) => self.actor_budgets.insert(id, budget),
+            None => self.actor_budgets.remove(&id),
+        };
+        true
+    }
+
+    /// Start a watchdog that reports when the loop of the commutator is stuck
+    /// on a single packet for longer than the limit, for example because a
+    /// handler blocks, or when packets are waiting that long while the loop
+    /// doesn't take any, for example because `run` isn't polled. The
+    /// watchdog stops when it is dropped.
+    pub fn watchdog(
+        &self,
+        limit: Duration,
+        report: impl FnMut(Stall) + Send + 'static,
+    ) -> Watchdog {
+        Watchdog::spawn(
+            self.heartbeat.clone(),
+            self.message_sender.queued(),
+            limit,
+            report,
+        )
     }
 
     pub fn set_interceptor(&mut self, interceptor: fn(&mut Self, M) -> InterceptResult<M>) {
@@ -209,6 +1088,70 @@ where
     }
 }
 
+impl<M> Drop for Commutator<M>
+where
+    M: Message,
+{
+    fn drop(&mut self) {
+        // The attached actors are dropped together with the commutator.
+        for liveness in self.liveness.values() {
+            liveness.terminate();
+        }
+        self.shut_down();
+    }
+}
+
+impl<M> Default for Commutator<M>
+where
+    M: Message,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add an actor to the list of a key, such as the members of a group, unless
+/// it is in the list already. Returns `false` if it was.
+fn add_to_list<K>(lists: &mut HashMap<K, Vec<ActorId>>, key: K, id: ActorId) -> bool
+where
+    K: Eq + Hash,
+{
+    let list = lists.entry(key).or_default();
+    if list.contains(&id) {
+        false
+    } else {
+        list.push(id);
+        true
+    }
+}
+
+/// Remove an actor from the list of a key, and the list if it is empty then.
+/// Returns `false` if the actor wasn't in the list.
+fn remove_from_list<K>(lists: &mut HashMap<K, Vec<ActorId>>, key: &K, id: ActorId) -> bool
+where
+    K: Eq + Hash,
+{
+    let list = match lists.get_mut(key) {
+        Some(list) => list,
+        None => return false,
+    };
+    let len = list.len();
+    list.retain(|other| *other != id);
+    let removed = list.len() != len;
+    if list.is_empty() {
+        lists.remove(key);
+    }
+    removed
+}
+
+/// Remove an actor from all lists, and the lists that are empty then.
+fn remove_from_lists<K>(lists: &mut HashMap<K, Vec<ActorId>>, id: ActorId) {
+    for list in lists.values_mut() {
+        list.retain(|other| *other != id);
+    }
+    lists.retain(|_, list| !list.is_empty());
+}
+
 pub enum InterceptResult<T> {
     Pass(T),
     Interception,
@@ -0,0 +1,201 @@
+use std::fmt::{self, Write};
+
+use crate::actor::ActorId;
+use crate::message::GroupId;
+
+/// Description of an attached actor, see `Commutator::introspect`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActorInfo {
+    pub id: ActorId,
+    /// The names the actor is registered under, sorted alphabetically.
+    pub names: Vec<String>,
+    /// The name of the type of the actor.
+    pub type_name: &'static str,
+    /// The message types the actor is subscribed to, in declaration order.
+    pub subscriptions: Vec<&'static str>,
+    /// The number of predicate subscriptions of the actor.
+    pub predicates: usize,
+    /// The groups the actor joined.
+    pub groups: Vec<GroupId>,
+    /// The actor that spawned the actor, if it is a child.
+    pub parent: Option<ActorId>,
+    /// The number of messages the actor handled.
+    pub handled: u64,
+}
+
+impl ActorInfo {
+    /// Get a label for the actor: its first name, or else its type name
+    /// without module paths.
+    pub fn label(&self) -> String {
+        match self.names.first() {
+            Some(name) => name.clone(),
+            None => short_type_name(self.type_name),
+        }
+    }
+}
+
+/// Snapshot of the actors of a commutator and their subscriptions, that can
+/// be rendered as a Graphviz DOT or Mermaid graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    /// The message types, in declaration order.
+    pub message_types: Vec<&'static str>,
+    /// The attached actors, in attach order.
+    pub actors: Vec<ActorInfo>,
+    /// The number of envelopes and control commands that were sent to the
+    /// commutator and not received yet.
+    pub queue_length: usize,
+    /// The number of envelopes the commutator received.
+    pub received: u64,
+}
+
+impl Topology {
+    /// Get the description of an attached actor.
+    pub fn actor(&self, id: ActorId) -> Option<&ActorInfo> {
+        self.actors.iter().find(|actor| actor.id == id)
+    }
+
+    /// Get the message types that have subscribers.
+    fn subscribed_types(&self) -> Vec<&'static str> {
+        self.message_types
+            .iter()
+            .copied()
+            .filter(|name| {
+                self.actors
+                    .iter()
+                    .any(|actor| actor.subscriptions.contains(name))
+            })
+            .collect()
+    }
+
+    /// Check if any actor has a predicate subscription.
+    fn has_predicates(&self) -> bool {
+        self.actors.iter().any(|actor| actor.predicates > 0)
+    }
+
+    /// Render the topology as a Graphviz DOT graph. Message types are
+    /// ellipses with an edge to every subscriber, actors are boxes, and
+    /// dashed edges lead from parents to the children they spawned. Predicate
+    /// subscriptions are edges from a single `predicate` diamond, labeled
+    /// with their number if an actor has more than one.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        self.write_dot(&mut dot)
+            .expect("writing to a string can't fail");
+        dot
+    }
+
+    fn write_dot(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "digraph armature {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        for name in self.subscribed_types() {
+            writeln!(
+                out,
+                "    \"type_{}\" [label=\"{}\", shape=ellipse];",
+                name, name
+            )?;
+        }
+        if self.has_predicates() {
+            writeln!(
+                out,
+                "    \"predicate\" [label=\"predicate\", shape=diamond];"
+            )?;
+        }
+        for actor in self.actors.iter() {
+            writeln!(
+                out,
+                "    \"actor_{}\" [label=\"{}\", shape=box];",
+                actor.id,
+                escape_dot(&actor.label())
+            )?;
+        }
+        for actor in self.actors.iter() {
+            for name in actor.subscriptions.iter() {
+                writeln!(out, "    \"type_{}\" -> \"actor_{}\";", name, actor.id)?;
+            }
+            match actor.predicates {
+                0 => {}
+                1 => writeln!(out, "    \"predicate\" -> \"actor_{}\";", actor.id)?,
+                count => writeln!(
+                    out,
+                    "    \"predicate\" -> \"actor_{}\" [label=\"{}\"];",
+                    actor.id, count
+                )?,
+            }
+            if let Some(parent) = actor.parent {
+                writeln!(
+                    out,
+                    "    \"actor_{}\" -> \"actor_{}\" [style=dashed];",
+                    parent, actor.id
+                )?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Render the topology as a Mermaid flowchart, with the same nodes and
+    /// edges as `to_dot`.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::new();
+        self.write_mermaid(&mut mermaid)
+            .expect("writing to a string can't fail");
+        mermaid
+    }
+
+    fn write_mermaid(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "flowchart LR")?;
+        for name in self.subscribed_types() {
+            writeln!(out, "    type_{}([\"{}\"])", name, name)?;
+        }
+        if self.has_predicates() {
+            writeln!(out, "    predicate{{\"predicate\"}}")?;
+        }
+        for actor in self.actors.iter() {
+            writeln!(
+                out,
+                "    actor_{}[\"{}\"]",
+                actor.id,
+                escape_mermaid(&actor.label())
+            )?;
+        }
+        for actor in self.actors.iter() {
+            for name in actor.subscriptions.iter() {
+                writeln!(out, "    type_{} --> actor_{}", name, actor.id)?;
+            }
+            match actor.predicates {
+                0 => {}
+                1 => writeln!(out, "    predicate --> actor_{}", actor.id)?,
+                count => writeln!(out, "    predicate -->|{}| actor_{}", count, actor.id)?,
+            }
+            if let Some(parent) = actor.parent {
+                writeln!(out, "    actor_{} -.-> actor_{}", parent, actor.id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove the module paths from a type name, also inside generic arguments.
+fn short_type_name(type_name: &str) -> String {
+    let mut short = String::new();
+    let mut segment = String::new();
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            short.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(segment.rsplit("::").next().unwrap_or_default());
+    short
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(label: &str) -> String {
+    label.replace('"', "#quot;")
+}
@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use crate::actor::ActorId;
+use crate::message::*;
+
+/// Entry of a journal.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "M: serde::Serialize",
+        deserialize = "M: serde::Deserialize<'de>"
+    ))
+)]
+pub enum Entry<M>
+where
+    M: Message,
+{
+    /// An actor was attached. The actors that are attached when the recording
+    /// starts are listed first, in attach order.
+    Attached { time: Duration, id: ActorId },
+    /// The commutator received an envelope, before it was intercepted.
+    Received {
+        time: Duration,
+        envelope: Envelope<M>,
+    },
+}
+
+impl<M> Entry<M>
+where
+    M: Message,
+{
+    /// Get the time of the entry, according to the clock of the commutator.
+    pub fn time(&self) -> Duration {
+        match self {
+            Entry::Attached { time, .. } | Entry::Received { time, .. } => *time,
+        }
+    }
+}
+
+/// Receives the entries of a commutator that is recording, see
+/// `Commutator::record`.
+pub trait Recorder<M>: Send
+where
+    M: Message,
+{
+    /// Record an entry.
+    fn record(&mut self, entry: &Entry<M>);
+
+    /// Turn the recorder into an in-memory journal, if it is one.
+    fn into_journal(self: Box<Self>) -> Option<Journal<M>> {
+        None
+    }
+}
+
+/// In-memory journal of the envelopes a commutator received, that can be
+/// replayed into another commutator with `Commutator::replay`.
+#[derive(Clone, Debug)]
+pub struct Journal<M>
+where
+    M: Message,
+{
+    entries: Vec<Entry<M>>,
+}
+
+impl<M> Journal<M>
+where
+    M: Message,
+{
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Get the entries, in recording order.
+    pub fn entries(&self) -> &[Entry<M>] {
+        &self.entries
+    }
+
+    /// Add an entry.
+    pub fn push(&mut self, entry: Entry<M>) {
+        self.entries.push(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn into_entries(self) -> Vec<Entry<M>> {
+        self.entries
+    }
+}
+
+impl<M> Default for Journal<M>
+where
+    M: Message,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Recorder<M> for Journal<M>
+where
+    M: Message + Clone,
+{
+    fn record(&mut self, entry: &Entry<M>) {
+        let entry = match entry {
+            Entry::Received { time, envelope } => Entry::Received {
+                time: *time,
+                envelope: envelope.without_reply(),
+            },
+            entry => entry.clone(),
+        };
+        self.entries.push(entry);
+    }
+
+    fn into_journal(self: Box<Self>) -> Option<Journal<M>> {
+        Some(*self)
+    }
+}
+
+/// Maps the ids of the recorded actors to the ids of the actors of the
+/// commutator the journal is replayed into. The n-th attached actor of the
+/// recording corresponds to the n-th attached actor of the replay.
+pub(crate) struct IdMap {
+    recorded: Vec<ActorId>,
+    attached: Vec<ActorId>,
+}
+
+impl IdMap {
+    pub(crate) fn new(attached: Vec<ActorId>) -> Self {
+        Self {
+            recorded: Vec::new(),
+            attached,
+        }
+    }
+
+    pub(crate) fn recorded(&mut self, id: ActorId) {
+        self.recorded.push(id);
+    }
+
+    pub(crate) fn attached(&mut self, id: ActorId) {
+        self.attached.push(id);
+    }
+
+    pub(crate) fn translate(&self, id: ActorId) -> ActorId {
+        self.recorded
+            .iter()
+            .position(|recorded| *recorded == id)
+            .and_then(|position| self.attached.get(position))
+            .copied()
+            .unwrap_or(id)
+    }
+
+    pub(crate) fn translate_envelope<M>(&self, envelope: Envelope<M>) -> Envelope<M>
+    where
+        M: Message,
+    {
+        let origin = match envelope.origin {
+            Origin::Actor(id) => Origin::Actor(self.translate(id)),
+            origin => origin,
+        };
+        let destination = match envelope.destination {
+            Destination::AllExcept(id) => Destination::AllExcept(self.translate(id)),
+            Destination::Single(id) => Destination::Single(self.translate(id)),
+            Destination::Many(ids) => {
+                Destination::Many(ids.into_iter().map(|id| self.translate(id)).collect())
+            }
+            destination => destination,
+        };
+        Envelope::new(origin, destination, envelope.message)
+    }
+}
+
+#[cfg(feature = "journal")]
+mod file {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader, BufWriter, Write};
+    use std::path::Path;
+
+    use super::*;
+
+    /// Recorder that writes a journal to a file, one JSON entry per line.
+    /// Every entry is flushed right away, so the journal is complete up to
+    /// the last received envelope if the process crashes.
+    pub struct JournalFile {
+        writer: BufWriter<File>,
+    }
+
+    impl JournalFile {
+        /// Create the journal file, replacing an existing file.
+        pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+            Ok(Self {
+                writer: BufWriter::new(File::create(path)?),
+            })
+        }
+
+        fn write<M>(&mut self, entry: &Entry<M>) -> io::Result<()>
+        where
+            M: Message + Serialize,
+        {
+            serde_json::to_writer(&mut self.writer, entry)?;
+            self.writer.write_all(b"\n")?;
+            self.writer.flush()
+        }
+    }
+
+    impl<M> Recorder<M> for JournalFile
+    where
+        M: Message + Serialize,
+    {
+        fn record(&mut self, entry: &Entry<M>) {
+            if let Err(error) = self.write(entry) {
+                log::error!("can't write journal entry: {}", error);
+            }
+        }
+    }
+
+    impl<M> Journal<M>
+    where
+        M: Message + Serialize + DeserializeOwned,
+    {
+        /// Load a journal that was written by a `JournalFile`.
+        pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+            let mut journal = Self::new();
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                journal.push(serde_json::from_str(&line)?);
+            }
+            Ok(journal)
+        }
+
+        /// Write the journal to a file, in the format of a `JournalFile`.
+        pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+            let mut file = JournalFile::create(path)?;
+            for entry in self.entries() {
+                file.write(entry)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "journal")]
+pub use file::JournalFile;
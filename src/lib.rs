@@ -1,4 +1,9 @@
+extern crate self as armature;
+
 pub mod actor;
+pub mod actor_ref;
+pub mod bridge;
+pub mod clock;
 /// Armature is a framework to design event-driven systems with stateful
 /// actors.
 ///
@@ -10,14 +15,63 @@ pub mod actor;
 /// **Stators**: actors that contain a hierarchial state machine that responds
 /// to incoming events and are able to spawn tasks inside the async runtime.
 pub mod commutator;
+pub mod introspection;
+pub mod journal;
 pub mod message;
+pub mod metrics;
+pub mod persistence;
 pub mod publisher;
-mod store;
+pub mod registry;
+/// The channel of a commutator. The channel carries packets: envelopes that
+/// are dispatched to the actors, and control commands, such as monitoring or
+/// spawning an actor, that the commutator executes itself, in the order in
+/// which they were sent.
+///
+/// Besides sending, a `Sender` gives access to the registry, the clock and
+/// the store of its commutator.
+pub mod sender;
+pub mod store;
+pub mod subscription;
+pub mod system;
+#[cfg(feature = "transport")]
+pub mod transport;
+pub mod typed;
 pub mod utils;
+pub mod watchdog;
 
-pub use actor::{Actor, ActorObject};
-pub use commutator::{Commutator, InterceptResult, Interceptor, Receiver, Sender};
-pub use message::{Destination, Envelope, Message, MessageType, Origin};
+pub use actor::{
+    Actor, ActorFactory, ActorId, ActorObject, CloneActor, CloneableActorObject, HandlerComponent,
+};
+pub use actor_ref::ActorRef;
+pub use bridge::Bridge;
+pub use clock::Clock;
+pub use commutator::{Commutator, InterceptResult, Interceptor};
+pub use introspection::{ActorInfo, Topology};
+#[cfg(feature = "journal")]
+pub use journal::JournalFile;
+pub use journal::{Journal, Recorder};
+pub use message::{
+    Destination, Envelope, GroupId, Message, MessageType, MessageTypeInfo, Origin, Priority,
+};
+pub use metrics::{ActorMetrics, Histogram, Metrics};
+#[cfg(feature = "persistence")]
+pub use persistence::FileStorage;
+pub use persistence::{MemoryStorage, Persisted, Persistent, Recovery, Storage};
 pub use publisher::{DeputyPublisher, Publisher};
+pub use registry::{Registry, RegistryError};
+pub use sender::{Receiver, SendError, Sender};
+pub use store::{Key, Store};
+pub use subscription::{
+    Filter, Subscription, SubscriptionFilter, SubscriptionId, SubscriptionTable,
+};
+pub use system::{SystemMessage, TerminationReason};
+#[cfg(feature = "transport")]
+pub use transport::{Endpoint, Transport};
+pub use typed::{Typed, TypedActor};
+
+pub use watchdog::{Stall, Watchdog};
 
 pub use armature_macro::MessageType;
+
+#[cfg(feature = "serde")]
+pub use serde;
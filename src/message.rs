@@ -1,38 +1,303 @@
-use std::{fmt::Debug, hash::Hash};
+use futures::channel::oneshot;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::{fmt, fmt::Debug, hash::Hash};
 
-use crate::actor::ActorObject;
+use crate::actor::ActorId;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Destination {
+    /// All the actors that are subscribed to the message type.
     All,
-    Single(usize),
+    /// All the actors that are subscribed to the message type, except the
+    /// given actor.
+    AllExcept(ActorId),
+    /// A single actor, regardless of its subscriptions.
+    Single(ActorId),
+    /// The given actors, regardless of their subscriptions.
+    Many(Vec<ActorId>),
+    /// The actors that joined the named group, regardless of their
+    /// subscriptions.
+    Group(GroupId),
+}
+
+/// Name of a group of actors that can be used as a destination.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupId(Cow<'static, str>);
+
+impl GroupId {
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&'static str> for GroupId {
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for GroupId {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Origin {
     Anonymous,
-    Actor(usize),
+    Actor(ActorId),
+    /// The commutator itself.
+    System,
+    /// A `Bridge` forwarded the message from another commutator. Both ids
+    /// belong to the other commutator: `bridge` is the id of the bridge, and
+    /// `actor` the id of the actor that sent the message, if it was sent by
+    /// an actor.
+    Bridged {
+        bridge: ActorId,
+        actor: Option<ActorId>,
+    },
 }
 
 /// Envelope wraps an event and defines its destination.
+///
+/// An envelope also carries state that is private to the crate, so it can't
+/// be built with a struct literal or destructured exhaustively outside of it.
+/// Envelopes are built with `Envelope::new`, or copied without their reply
+/// address with `Envelope::without_reply`:
+///
+/// ```compile_fail
+/// # use armature::{Destination, Envelope, MessageType, Origin};
+/// # #[derive(MessageType)]
+/// # #[message_type(name = "Signal")]
+/// # pub enum Event { Tick }
+/// # impl armature::Message for Event { type MessageType = Signal; }
+/// # impl armature::MessageType for Signal { type Message = Event; }
+/// let envelope = Envelope {
+///     origin: Origin::Anonymous,
+///     destination: Destination::All,
+///     message: Event::Tick,
+///     reply_to: None,
+/// };
+/// ```
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "M: serde::Serialize",
+        deserialize = "M: serde::Deserialize<'de>"
+    ))
+)]
+#[non_exhaustive]
 pub struct Envelope<M: Message> {
     pub origin: Origin,
     pub destination: Destination,
     pub message: M,
+    /// Where to send the reply to, if the message was sent with
+    /// `ActorRef::ask`. The reply address is local to the process, so it is
+    /// not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reply_to: Option<ReplyTo<M>>,
+    /// The span in which the envelope was sent. Spans are local to the
+    /// process, so the span is not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) span: SendSpan,
+    /// The commutators the envelope was forwarded from by bridges, so it is
+    /// not forwarded back to any of them. Like the span, this is local to the
+    /// process and not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) bridged: Box<[usize]>,
+}
+
+/// The span in which an envelope was sent, which becomes the parent of the
+/// span of its dispatch. It is empty without the `tracing` feature, so the
+/// envelope has the same fields with and without the feature.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SendSpan(#[cfg(feature = "tracing")] pub(crate) Option<tracing::Span>);
+
+impl SendSpan {
+    /// Get the current span, if there is one.
+    pub(crate) fn current() -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let current = tracing::Span::current();
+            Self((!current.is_none()).then_some(current))
+        }
+        #[cfg(not(feature = "tracing"))]
+        Self()
+    }
+}
+
+impl<M: Message> Envelope<M> {
+    pub fn new(origin: Origin, destination: Destination, message: M) -> Self {
+        Self {
+            origin,
+            destination,
+            message,
+            reply_to: None,
+            span: SendSpan::default(),
+            bridged: Box::default(),
+        }
+    }
+
+    /// Check if the sender of the message expects a reply.
+    pub fn expects_reply(&self) -> bool {
+        self.reply_to
+            .as_ref()
+            .is_some_and(|reply_to| !reply_to.is_used())
+    }
+
+    /// Reply to the message. The message is given back if the sender doesn't
+    /// expect a reply, or if the message was already replied to.
+    pub fn reply(&self, message: M) -> Result<(), M> {
+        match &self.reply_to {
+            Some(reply_to) => reply_to.send(message),
+            None => Err(message),
+        }
+    }
+
+    /// Copy the envelope without its reply address. Envelopes that are kept,
+    /// for example in a journal or an event log, are copied this way, so
+    /// they don't keep the reply of an `ActorRef::ask` pending.
+    pub fn without_reply(&self) -> Envelope<M>
+    where
+        M: Clone,
+    {
+        Envelope::new(self.origin, self.destination.clone(), self.message.clone())
+    }
+}
+
+/// Function that delivers a reply, and gives it back if it can't.
+type Reply<M> = Box<dyn FnOnce(M) -> Result<(), M> + Send>;
+
+/// One-time slot for the reply to a message. Clones of an envelope share the
+/// same slot, so only the first reply is delivered.
+pub struct ReplyTo<M> {
+    reply: Arc<Mutex<Option<Reply<M>>>>,
+}
+
+impl<M> ReplyTo<M>
+where
+    M: Send + 'static,
+{
+    pub(crate) fn new(sender: oneshot::Sender<M>) -> Self {
+        Self::with(move |message| sender.send(message))
+    }
+
+    /// Create a slot that delivers the reply with the given function.
+    pub(crate) fn with(reply: impl FnOnce(M) -> Result<(), M> + Send + 'static) -> Self {
+        Self {
+            reply: Arc::new(Mutex::new(Some(Box::new(reply)))),
+        }
+    }
+}
+
+impl<M> ReplyTo<M> {
+    pub(crate) fn send(&self, message: M) -> Result<(), M> {
+        match self.reply.lock().unwrap().take() {
+            Some(reply) => reply(message),
+            None => Err(message),
+        }
+    }
+
+    fn is_used(&self) -> bool {
+        self.reply.lock().unwrap().is_none()
+    }
+}
+
+impl<M> Clone for ReplyTo<M> {
+    fn clone(&self) -> Self {
+        Self {
+            reply: self.reply.clone(),
+        }
+    }
+}
+
+impl<M> fmt::Debug for ReplyTo<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ReplyTo")
+            .field("used", &self.is_used())
+            .finish()
+    }
 }
 
 /// Trait that must be implemented on the event enum.
 pub trait Message
 where
-    Self: Sized + Send,
+    Self: Sized + Send + 'static,
 {
     type MessageType: MessageType<Message = Self>;
 }
 
 pub trait MessageType
 where
-    Self: Eq + Hash + Copy + Send + Clone + for<'a> From<&'a Self::Message>,
+    Self: Eq + Hash + Copy + Send + Clone + MessageTypeInfo + for<'a> From<&'a Self::Message>,
 {
     type Message: Message<MessageType = Self>;
 }
+
+/// Static information about the variants of a message type, so they can be
+/// enumerated, logged and used as array indices without hashing. This trait
+/// is implemented by `#[derive(MessageType)]`.
+pub trait MessageTypeInfo
+where
+    Self: Sized + 'static,
+{
+    /// All the message types, in declaration order.
+    const ALL: &'static [Self];
+
+    /// The number of message types.
+    const COUNT: usize = Self::ALL.len();
+
+    /// Get the name of the message type.
+    fn name(&self) -> &'static str;
+
+    /// Get the dense index of the message type, which is its position in
+    /// `ALL`. The index is always smaller than `COUNT`.
+    fn index(&self) -> usize;
+
+    /// Get the priority of the message type.
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    /// Get the named groups the message type belongs to.
+    fn groups(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Check if the message type belongs to the given group.
+    fn in_group(&self, group: &str) -> bool {
+        self.groups().contains(&group)
+    }
+
+    /// Check if the message type is excluded from subscriptions. Skipped
+    /// message types can still be sent to a specific actor.
+    fn is_skipped(&self) -> bool {
+        false
+    }
+}
+
+/// Priority of a message type, set with `#[message_type(priority = "...")]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
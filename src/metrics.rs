@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::actor::ActorId;
+use crate::message::{Message, MessageTypeInfo};
+
+/// Upper bounds of the buckets of handling time histograms.
+pub const HANDLING_TIME_BUCKETS: [Duration; 7] = [
+    Duration::from_micros(1),
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// Histogram of durations, with the buckets of `HANDLING_TIME_BUCKETS`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// The number of observations per bucket. The last bucket counts the
+    /// observations that exceed every bound.
+    buckets: [u64; HANDLING_TIME_BUCKETS.len() + 1],
+    sum: Duration,
+    count: u64,
+}
+
+impl Histogram {
+    /// Add an observation.
+    pub fn observe(&mut self, duration: Duration) {
+        let bucket = HANDLING_TIME_BUCKETS
+            .iter()
+            .position(|bound| duration <= *bound)
+            .unwrap_or(HANDLING_TIME_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += duration;
+        self.count += 1;
+    }
+
+    /// Get the cumulative counts of the buckets, paired with their upper
+    /// bound. The last bucket has no bound, and its count is the total count.
+    pub fn cumulative(&self) -> Vec<(Option<Duration>, u64)> {
+        let mut total = 0;
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                total += count;
+                (HANDLING_TIME_BUCKETS.get(bucket).copied(), total)
+            })
+            .collect()
+    }
+
+    /// Get the sum of the observed durations.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Get the number of observations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Metrics of an attached actor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActorMetrics {
+    pub id: ActorId,
+    /// The first name the actor is registered under, if any.
+    pub name: Option<String>,
+    /// The number of messages the actor handled.
+    pub handled: u64,
+    /// The number of messages the actor took longer to handle than its
+    /// budget, see `Commutator::set_handle_budget`.
+    pub over_budget: u64,
+    /// How long the actor took to handle the messages.
+    pub handling_time: Histogram,
+}
+
+/// Snapshot of the metrics of a commutator, see `Commutator::metrics`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of envelopes and control commands that were sent to the
+    /// commutator and not received yet.
+    pub queue_length: usize,
+    /// The number of envelopes the commutator received.
+    pub received: u64,
+    /// The number of dispatched envelopes per message type, in declaration
+    /// order.
+    pub dispatched: Vec<(&'static str, u64)>,
+    /// The number of envelopes that were dropped without being dispatched,
+    /// because the interceptor consumed them or because they were sent while
+    /// a journal was replayed.
+    pub dropped: u64,
+    /// The number of dispatched envelopes that didn't reach any actor.
+    pub dead_letters: u64,
+    /// The metrics of the attached actors, in attach order.
+    pub actors: Vec<ActorMetrics>,
+}
+
+/// Counters that the commutator updates while it runs.
+pub(crate) struct Counters {
+    pub(crate) received: u64,
+    pub(crate) dispatched: Vec<u64>,
+    pub(crate) dropped: u64,
+    pub(crate) dead_letters: u64,
+    pub(crate) actors: HashMap<ActorId, ActorCounters>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct ActorCounters {
+    pub(crate) handled: u64,
+    pub(crate) over_budget: u64,
+    pub(crate) handling_time: Histogram,
+}
+
+impl Counters {
+    pub(crate) fn new<M>() -> Self
+    where
+        M: Message,
+    {
+        Self {
+            received: 0,
+            dispatched: vec![0; M::MessageType::COUNT],
+            dropped: 0,
+            dead_letters: 0,
+            actors: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn handled(&mut self, id: ActorId, duration: Duration, over_budget: bool) {
+        let counters = self.actors.entry(id).or_default();
+        counters.handled += 1;
+        if over_budget {
+            counters.over_budget += 1;
+        }
+        counters.handling_time.observe(duration);
+    }
+}
+
+#[cfg(feature = "prometheus")]
+mod prometheus {
+    use std::fmt::Write;
+
+    use super::*;
+
+    impl Metrics {
+        /// Render the metrics in the Prometheus text exposition format. Every
+        /// metric name starts with the given prefix.
+        pub fn to_prometheus(&self, prefix: &str) -> String {
+            let mut text = String::new();
+            self.write_prometheus(&mut text, prefix)
+                .expect("writing to a string can't fail");
+            text
+        }
+
+        fn write_prometheus(&self, out: &mut String, prefix: &str) -> std::fmt::Result {
+            writeln!(
+                out,
+                "# HELP {}_queue_length Packets waiting in the queue.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_queue_length gauge", prefix)?;
+            writeln!(out, "{}_queue_length {}", prefix, self.queue_length)?;
+
+            writeln!(out, "# HELP {}_received_total Envelopes received.", prefix)?;
+            writeln!(out, "# TYPE {}_received_total counter", prefix)?;
+            writeln!(out, "{}_received_total {}", prefix, self.received)?;
+
+            writeln!(
+                out,
+                "# HELP {}_dispatched_total Envelopes dispatched per message type.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_dispatched_total counter", prefix)?;
+            for (message_type, count) in self.dispatched.iter() {
+                writeln!(
+                    out,
+                    "{}_dispatched_total{{message_type=\"{}\"}} {}",
+                    prefix,
+                    escape(message_type),
+                    count
+                )?;
+            }
+
+            writeln!(
+                out,
+                "# HELP {}_dropped_total Envelopes dropped before dispatch.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_dropped_total counter", prefix)?;
+            writeln!(out, "{}_dropped_total {}", prefix, self.dropped)?;
+
+            writeln!(
+                out,
+                "# HELP {}_dead_letters_total Envelopes that reached no actor.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_dead_letters_total counter", prefix)?;
+            writeln!(out, "{}_dead_letters_total {}", prefix, self.dead_letters)?;
+
+            writeln!(
+                out,
+                "# HELP {}_handled_total Messages handled per actor.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_handled_total counter", prefix)?;
+            for actor in self.actors.iter() {
+                writeln!(
+                    out,
+                    "{}_handled_total{{{}}} {}",
+                    prefix,
+                    labels(actor),
+                    actor.handled
+                )?;
+            }
+
+            writeln!(
+                out,
+                "# HELP {}_over_budget_total Messages handled over budget per actor.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_over_budget_total counter", prefix)?;
+            for actor in self.actors.iter() {
+                writeln!(
+                    out,
+                    "{}_over_budget_total{{{}}} {}",
+                    prefix,
+                    labels(actor),
+                    actor.over_budget
+                )?;
+            }
+
+            writeln!(
+                out,
+                "# HELP {}_handling_seconds Time actors took to handle a message.",
+                prefix
+            )?;
+            writeln!(out, "# TYPE {}_handling_seconds histogram", prefix)?;
+            for actor in self.actors.iter() {
+                let labels = labels(actor);
+                for (bound, count) in actor.handling_time.cumulative() {
+                    let le = match bound {
+                        Some(bound) => bound.as_secs_f64().to_string(),
+                        None => String::from("+Inf"),
+                    };
+                    writeln!(
+                        out,
+                        "{}_handling_seconds_bucket{{{},le=\"{}\"}} {}",
+                        prefix, labels, le, count
+                    )?;
+                }
+                writeln!(
+                    out,
+                    "{}_handling_seconds_sum{{{}}} {}",
+                    prefix,
+                    labels,
+                    actor.handling_time.sum().as_secs_f64()
+                )?;
+                writeln!(
+                    out,
+                    "{}_handling_seconds_count{{{}}} {}",
+                    prefix,
+                    labels,
+                    actor.handling_time.count()
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    fn labels(actor: &ActorMetrics) -> String {
+        match &actor.name {
+            Some(name) => format!("actor=\"{}\",name=\"{}\"", actor.id, escape(name)),
+            None => format!("actor=\"{}\"", actor.id),
+        }
+    }
+
+    /// Escape a label value.
+    fn escape(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::actor::{Actor, ActorId, HandlerComponent};
+use crate::message::*;
+use crate::subscription::{Subscription, SubscriptionFilter};
+use crate::system::SystemMessage;
+use crate::Sender;
+
+/// Actor whose state can be persisted, so it can be rebuilt after a restart.
+///
+/// A persistent actor is attached with `Commutator::attach_persistent`. Every
+/// message it handles is appended to its event log, and every
+/// `snapshot_interval` messages a snapshot of its state is saved. When it is
+/// attached again, for example after the process restarted, the latest
+/// snapshot is restored and the messages that were handled after it are
+/// handled again, right after `on_attach` is called.
+///
+/// While the actor is rebuilt, the envelopes it sends through the sender it
+/// gets in `on_attach` are dropped, since they were sent when the messages
+/// were handled the first time. Control commands, such as subscribing, are
+/// executed. The messages are handled again while the actor is attached, so
+/// a panic while the actor is rebuilt isn't caught by the commutator.
+pub trait Persistent: Actor {
+    type Snapshot;
+
+    /// Get the key under which the state of the actor is stored. The key has
+    /// to stay the same across restarts.
+    fn persistence_key(&self) -> String;
+
+    /// Take a snapshot of the state of the actor.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Restore the state of the actor from a snapshot.
+    fn restore(&mut self, snapshot: Self::Snapshot);
+
+    /// Get the number of handled messages after which a snapshot is saved.
+    fn snapshot_interval(&self) -> usize {
+        100
+    }
+}
+
+/// The latest snapshot of an actor and the envelopes it handled after it.
+pub struct Recovery<M, S>
+where
+    M: Message,
+{
+    pub snapshot: Option<S>,
+    pub events: Vec<Envelope<M>>,
+}
+
+/// Storage of the snapshots and event logs of persistent actors.
+pub trait Storage<M, S>: Send
+where
+    M: Message,
+{
+    /// Append a handled envelope to the event log of an actor. The reply
+    /// address of the envelope must not be kept.
+    fn append(&mut self, key: &str, envelope: &Envelope<M>) -> io::Result<()>;
+
+    /// Save a snapshot of an actor. The envelopes in its event log are no
+    /// longer needed.
+    fn save_snapshot(&mut self, key: &str, snapshot: &S) -> io::Result<()>;
+
+    /// Load the latest snapshot of an actor and the envelopes that were
+    /// appended after it.
+    fn load(&mut self, key: &str) -> io::Result<Recovery<M, S>>;
+}
+
+/// In-memory storage. Clones share their content, so a clone can be used to
+/// rebuild an actor in another commutator.
+pub struct MemoryStorage<M, S>
+where
+    M: Message,
+{
+    records: Arc<Mutex<HashMap<String, Recovery<M, S>>>>,
+}
+
+impl<M, S> MemoryStorage<M, S>
+where
+    M: Message,
+{
+    pub fn new() -> Self {
+        Self {
+            records: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<M, S> Default for MemoryStorage<M, S>
+where
+    M: Message,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, S> Clone for MemoryStorage<M, S>
+where
+    M: Message,
+{
+    fn clone(&self) -> Self {
+        Self {
+            records: self.records.clone(),
+        }
+    }
+}
+
+impl<M, S> Storage<M, S> for MemoryStorage<M, S>
+where
+    M: Message + Clone,
+    S: Clone + Send,
+{
+    fn append(&mut self, key: &str, envelope: &Envelope<M>) -> io::Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.entry(key.to_string()).or_insert(Recovery {
+            snapshot: None,
+            events: Vec::new(),
+        });
+        record.events.push(envelope.without_reply());
+        Ok(())
+    }
+
+    fn save_snapshot(&mut self, key: &str, snapshot: &S) -> io::Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let record = Recovery {
+            snapshot: Some(snapshot.clone()),
+            events: Vec::new(),
+        };
+        records.insert(key.to_string(), record);
+        Ok(())
+    }
+
+    fn load(&mut self, key: &str) -> io::Result<Recovery<M, S>> {
+        let records = self.records.lock().unwrap();
+        Ok(match records.get(key) {
+            Some(record) => Recovery {
+                snapshot: record.snapshot.clone(),
+                events: record.events.clone(),
+            },
+            None => Recovery {
+                snapshot: None,
+                events: Vec::new(),
+            },
+        })
+    }
+}
+
+/// Adapter that attaches a persistent actor to a commutator, and persists it
+/// in a storage.
+///
+/// A message is appended to the event log after it was handled, so a message
+/// that makes the actor panic isn't handled again when the actor is rebuilt.
+pub struct Persisted<A, S>
+where
+    A: Persistent,
+{
+    actor: A,
+    storage: S,
+    key: String,
+    /// The number of messages that were handled since the latest snapshot.
+    handled: usize,
+    /// Set while the actor is rebuilt, to drop the envelopes it sends.
+    recovering: Arc<AtomicBool>,
+}
+
+impl<A, S> Persisted<A, S>
+where
+    A: Persistent,
+    S: Storage<A::Message, A::Snapshot>,
+{
+    pub fn new(actor: A, storage: S) -> Self {
+        let key = actor.persistence_key();
+        Self {
+            actor,
+            storage,
+            key,
+            handled: 0,
+            recovering: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Get the persistent actor.
+    pub fn get_ref(&self) -> &A {
+        &self.actor
+    }
+
+    /// Get the persistent actor mutably.
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.actor
+    }
+
+    /// Unwrap the persistent actor.
+    pub fn into_inner(self) -> A {
+        self.actor
+    }
+
+    /// Save a snapshot of the actor right away.
+    pub fn save_snapshot(&mut self) -> io::Result<()> {
+        self.storage
+            .save_snapshot(&self.key, &self.actor.snapshot())?;
+        self.handled = 0;
+        Ok(())
+    }
+
+    /// Restore the latest snapshot and handle the messages of the event log,
+    /// while the envelopes the actor sends are dropped.
+    fn recover(&mut self) -> io::Result<()> {
+        let recovery = self.storage.load(&self.key)?;
+        if let Some(snapshot) = recovery.snapshot {
+            self.actor.restore(snapshot);
+        }
+        self.recovering.store(true, Ordering::Relaxed);
+        for envelope in recovery.events.iter() {
+            self.actor.handle(envelope);
+        }
+        self.recovering.store(false, Ordering::Relaxed);
+        self.handled = recovery.events.len();
+        Ok(())
+    }
+}
+
+impl<A, S> Actor for Persisted<A, S>
+where
+    A: Persistent,
+    S: Storage<A::Message, A::Snapshot> + 'static,
+{
+    type Message = A::Message;
+
+    fn handle(&mut self, envelope: &Envelope<Self::Message>) {
+        self.actor.handle(envelope);
+        if let Err(error) = self.storage.append(&self.key, envelope) {
+            log::error!("can't append to the event log of {}: {}", self.key, error);
+        }
+        self.handled += 1;
+        if self.handled >= self.actor.snapshot_interval() {
+            if let Err(error) = self.save_snapshot() {
+                log::error!("can't save a snapshot of {}: {}", self.key, error);
+            }
+        }
+    }
+
+    fn handle_system(&mut self, envelope: &Envelope<SystemMessage>) {
+        self.actor.handle_system(envelope);
+    }
+
+    fn on_attach(&mut self, sender: &Sender<Self::Message>) {
+        self.actor
+            .on_attach(&sender.muted_by(self.recovering.clone()));
+        if let Err(error) = self.recover() {
+            log::error!("can't recover {}: {}", self.key, error);
+        }
+    }
+
+    fn on_detach(&mut self) {
+        self.actor.on_detach();
+    }
+
+    fn init(&mut self) {
+        self.actor.init();
+    }
+
+    fn deinit(&mut self) {
+        self.actor.deinit();
+    }
+
+    fn default_subscriptions(&self) -> Vec<<Self::Message as Message>::MessageType> {
+        self.actor.default_subscriptions()
+    }
+
+    fn initial_subscriptions(&self) -> Vec<Subscription<Self::Message>> {
+        self.actor.initial_subscriptions()
+    }
+
+    fn subscription_filters(&self) -> Vec<SubscriptionFilter<Self::Message>> {
+        self.actor.subscription_filters()
+    }
+
+    fn default_groups(&self) -> Vec<GroupId> {
+        self.actor.default_groups()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.actor.type_name()
+    }
+
+    fn id(&self) -> Option<ActorId> {
+        self.actor.id()
+    }
+
+    fn component(&self) -> Option<&HandlerComponent<Self::Message>> {
+        self.actor.component()
+    }
+
+    fn component_mut(&mut self) -> Option<&mut HandlerComponent<Self::Message>> {
+        self.actor.component_mut()
+    }
+
+    fn insert_subscription(&mut self, sig: <Self::Message as Message>::MessageType) {
+        self.actor.insert_subscription(sig);
+    }
+
+    fn remove_subscription(&mut self, sig: <Self::Message as Message>::MessageType) {
+        self.actor.remove_subscription(sig);
+    }
+}
+
+impl<A, S> fmt::Debug for Persisted<A, S>
+where
+    A: Persistent + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Persisted")
+            .field("actor", &self.actor)
+            .field("key", &self.key)
+            .field("handled", &self.handled)
+            .finish()
+    }
+}
+
+#[cfg(feature = "persistence")]
+mod file {
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{BufRead, BufReader, Write};
+    use std::marker::PhantomData;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    /// Storage in a directory. The snapshot of an actor is stored as JSON in
+    /// `<key>.snapshot`, and its event log as one JSON envelope per line in
+    /// `<key>.events`. Keys must be valid file names.
+    ///
+    /// Every envelope is stored with a sequence number, and a snapshot with
+    /// the sequence number of the last envelope it includes. The event log is
+    /// truncated after a snapshot was saved, but if that doesn't happen, for
+    /// example because the process crashed, the envelopes the snapshot
+    /// includes are skipped when the actor is loaded.
+    pub struct FileStorage<M, S> {
+        directory: PathBuf,
+        /// The sequence number of the last envelope of every key that was
+        /// used.
+        sequences: HashMap<String, u64>,
+        _marker: PhantomData<fn() -> (M, S)>,
+    }
+
+    /// A snapshot or an envelope, as it is stored.
+    #[derive(Serialize, Deserialize)]
+    struct Stored<T> {
+        sequence: u64,
+        value: T,
+    }
+
+    type Event<M> = Stored<Envelope<M>>;
+
+    impl<M, S> FileStorage<M, S> {
+        /// Create a storage in the given directory, which is created if it
+        /// doesn't exist.
+        pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+            fs::create_dir_all(directory.as_ref())?;
+            Ok(Self {
+                directory: directory.as_ref().to_path_buf(),
+                sequences: HashMap::new(),
+                _marker: PhantomData,
+            })
+        }
+
+        fn path(&self, key: &str, extension: &str) -> io::Result<PathBuf> {
+            if key.is_empty() || key.contains(['/', '\\', '.']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?} is not a valid persistence key", key),
+                ));
+            }
+            Ok(self.directory.join(format!("{}.{}", key, extension)))
+        }
+    }
+
+    impl<M, S> FileStorage<M, S>
+    where
+        M: Message + Serialize + DeserializeOwned,
+        S: Serialize + DeserializeOwned,
+    {
+        /// Get the sequence number of the last envelope of a key, which is
+        /// read from the files the first time the key is used.
+        fn sequence(&mut self, key: &str) -> io::Result<u64> {
+            if !self.sequences.contains_key(key) {
+                self.load(key)?;
+            }
+            Ok(self.sequences[key])
+        }
+
+        /// Read the snapshot of a key and the envelopes it doesn't include,
+        /// together with the sequence number of the last envelope.
+        fn read(&self, key: &str) -> io::Result<(u64, Recovery<M, S>)> {
+            let snapshot: Option<Stored<S>> = match File::open(self.path(key, "snapshot")?) {
+                Ok(file) => Some(serde_json::from_reader(BufReader::new(file))?),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(error),
+            };
+            let included = snapshot.as_ref().map_or(0, |snapshot| snapshot.sequence);
+            let mut sequence = included;
+            let mut events = Vec::new();
+            match File::open(self.path(key, "events")?) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        let line = line?;
+                        if line.is_empty() {
+                            continue;
+                        }
+                        let event: Event<M> = serde_json::from_str(&line)?;
+                        if event.sequence > included {
+                            sequence = event.sequence;
+                            events.push(event.value);
+                        }
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+            let recovery = Recovery {
+                snapshot: snapshot.map(|snapshot| snapshot.value),
+                events,
+            };
+            Ok((sequence, recovery))
+        }
+    }
+
+    impl<M, S> Storage<M, S> for FileStorage<M, S>
+    where
+        M: Message + Serialize + DeserializeOwned,
+        S: Serialize + DeserializeOwned,
+    {
+        fn append(&mut self, key: &str, envelope: &Envelope<M>) -> io::Result<()> {
+            let sequence = self.sequence(key)? + 1;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(key, "events")?)?;
+            let event = Stored {
+                sequence,
+                value: envelope,
+            };
+            let mut line = serde_json::to_vec(&event)?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+            self.sequences.insert(key.to_string(), sequence);
+            Ok(())
+        }
+
+        fn save_snapshot(&mut self, key: &str, snapshot: &S) -> io::Result<()> {
+            // Write the snapshot next to the previous one and rename it, so
+            // there always is a complete snapshot.
+            let sequence = self.sequence(key)?;
+            let path = self.path(key, "snapshot")?;
+            let partial = self.path(key, "snapshot-partial")?;
+            let mut file = File::create(&partial)?;
+            let snapshot = Stored {
+                sequence,
+                value: snapshot,
+            };
+            serde_json::to_writer(&mut file, &snapshot)?;
+            file.sync_all()?;
+            fs::rename(&partial, &path)?;
+            // The snapshot includes every envelope of the log. If the log
+            // isn't truncated, they are skipped when the actor is loaded.
+            File::create(self.path(key, "events")?)?;
+            Ok(())
+        }
+
+        fn load(&mut self, key: &str) -> io::Result<Recovery<M, S>> {
+            let (sequence, recovery) = self.read(key)?;
+            self.sequences.insert(key.to_string(), sequence);
+            Ok(recovery)
+        }
+    }
+}
+
+#[cfg(feature = "persistence")]
+pub use file::FileStorage;
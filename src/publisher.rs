@@ -1,7 +1,7 @@
+use crate::actor::ActorId;
 use crate::message::*;
 use crate::Actor;
 use crate::Sender;
-use futures::channel::mpsc;
 
 /// Trait for sending events to the commutator.
 pub trait Publisher {
@@ -16,24 +16,39 @@ pub trait Publisher {
 
     /// Publish a message to all actors.
     fn publish(&self, message: Self::Message) {
-        let envelope = Envelope {
-            origin: self.origin(),
-            destination: Destination::All,
-            message,
-        };
+        let envelope = Envelope::new(self.origin(), Destination::All, message);
         self.send(envelope);
     }
 
     /// Post a message to a specific actor.
-    fn post(&self, message: Self::Message, actor_id: usize) {
-        let envelope = Envelope {
-            origin: self.origin(),
-            destination: Destination::Single(actor_id),
-            message,
-        };
+    fn post(&self, message: Self::Message, actor_id: ActorId) {
+        let envelope = Envelope::new(self.origin(), Destination::Single(actor_id), message);
         self.send(envelope);
     }
 
+    /// Post a message to the given actors.
+    fn multicast(&self, message: Self::Message, actor_ids: Vec<ActorId>) {
+        let envelope = Envelope::new(self.origin(), Destination::Many(actor_ids), message);
+        self.send(envelope);
+    }
+
+    /// Post a message to the members of a group.
+    fn post_to_group(&self, message: Self::Message, group: impl Into<GroupId>) {
+        let envelope = Envelope::new(self.origin(), Destination::Group(group.into()), message);
+        self.send(envelope);
+    }
+
+    /// Publish a message to all actors, except the given one.
+    fn publish_except(&self, message: Self::Message, actor_id: ActorId) {
+        let envelope = Envelope::new(self.origin(), Destination::AllExcept(actor_id), message);
+        self.send(envelope);
+    }
+
+    /// Get the id of the actor that is registered under the given name.
+    fn resolve(&self, name: &str) -> Option<ActorId> {
+        self.sender().resolve(name)
+    }
+
     /// Send an envelope.
     fn send(&self, envelope: Envelope<Self::Message>) {
         self.sender()
@@ -42,15 +57,16 @@ pub trait Publisher {
     }
 
     /// Create a deputy publisher that is associated with the current actor.
-    fn deputy(&self) -> DeputyPublisher<<Self as Publisher>::Message>
+    /// Returns `None` if the id of the actor isn't known.
+    fn deputy(&self) -> Option<DeputyPublisher<<Self as Publisher>::Message>>
     where
         Self: Actor<Message = <Self as Publisher>::Message> + Sized,
     {
-        DeputyPublisher::from(self)
+        DeputyPublisher::of(self)
     }
 }
 
-impl<M: Message> Publisher for mpsc::UnboundedSender<Envelope<M>> {
+impl<M: Message> Publisher for Sender<M> {
     type Message = M;
 
     fn sender(&self) -> &Sender<Self::Message> {
@@ -66,29 +82,35 @@ where
     M: Message,
 {
     sender: Sender<M>,
-    actor_id: usize,
+    actor_id: ActorId,
 }
 
 impl<M> DeputyPublisher<M>
 where
     M: Message,
 {
-    pub fn actor_id(&self) -> usize {
-        self.actor_id
-    }
-}
-
-impl<A, M> From<&A> for DeputyPublisher<M>
-where
-    A: Actor<Message = M> + Publisher<Message = M>,
-    M: Message,
-{
-    fn from(actor: &A) -> Self {
+    /// Create a deputy. A deputy usually runs in a task that isn't part of
+    /// any span, so the envelopes it sends outside of any span are linked to
+    /// the span in which it was created.
+    pub(crate) fn new(sender: Sender<M>, actor_id: ActorId) -> Self {
         Self {
-            sender: actor.sender().clone(),
-            actor_id: actor.id(),
+            sender: sender.in_current_span(),
+            actor_id,
         }
     }
+
+    /// Create a deputy that publishes in name of an actor. Returns `None` if
+    /// the id of the actor isn't known, see `Actor::id`.
+    pub fn of<A>(actor: &A) -> Option<Self>
+    where
+        A: Actor<Message = M> + Publisher<Message = M>,
+    {
+        Some(Self::new(actor.sender().clone(), actor.id()?))
+    }
+
+    pub fn actor_id(&self) -> ActorId {
+        self.actor_id
+    }
 }
 
 impl<M> Publisher for DeputyPublisher<M>
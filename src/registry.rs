@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use crate::actor::ActorId;
+
+/// Registry of the names of the actors that are attached to a commutator. The
+/// registry is shared between the commutator and its senders, so actors can
+/// look each other up by name.
+#[derive(Debug, Default)]
+pub struct Registry {
+    names: RwLock<HashMap<String, ActorId>>,
+}
+
+impl Registry {
+    /// Get the id of the actor that is registered under the given name.
+    pub fn resolve(&self, name: &str) -> Option<ActorId> {
+        self.names.read().unwrap().get(name).copied()
+    }
+
+    /// Get the names an actor is registered under, sorted alphabetically.
+    pub fn names_of(&self, id: ActorId) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .names
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, actor_id)| **actor_id == id)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn register(&self, name: String, id: ActorId) -> Result<(), RegistryError> {
+        let mut names = self.names.write().unwrap();
+        match names.get(&name) {
+            Some(existing) if *existing != id => Err(RegistryError::NameTaken {
+                name,
+                id: *existing,
+            }),
+            _ => {
+                names.insert(name, id);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn unregister(&self, name: &str) -> Option<ActorId> {
+        self.names.write().unwrap().remove(name)
+    }
+
+    /// Release all the names of an actor.
+    pub(crate) fn remove_actor(&self, id: ActorId) {
+        self.names
+            .write()
+            .unwrap()
+            .retain(|_, actor_id| *actor_id != id);
+    }
+}
+
+/// Error returned when an actor can't be registered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The name is already taken by another actor.
+    NameTaken { name: String, id: ActorId },
+    /// No actor with the given id is attached.
+    NotAttached(ActorId),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            RegistryError::NameTaken { name, id } => {
+                write!(f, "name `{}` is already taken by actor {}", name, id)
+            }
+            RegistryError::NotAttached(id) => write!(f, "actor {} is not attached", id),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
@@ -0,0 +1,580 @@
+use futures::channel::mpsc;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::actor::{ActorFactory, ActorId, ActorObject};
+use crate::actor_ref::{ActorRef, Liveness};
+use crate::clock::Clock;
+use crate::message::*;
+use crate::registry::Registry;
+use crate::store::{Key, KeyId, Store, Value};
+use crate::subscription::{Filter, Subscription, SubscriptionId};
+
+/// Packet that travels through the channel of a commutator: either an
+/// envelope that is dispatched to the actors, or a control command that is
+/// executed by the commutator itself.
+pub enum Packet<M>
+where
+    M: Message,
+{
+    Envelope(Envelope<M>),
+    Control(Control<M>),
+}
+
+/// Control command that is executed by the commutator.
+pub struct Control<M>(pub(crate) Command<M>)
+where
+    M: Message;
+
+pub(crate) enum Command<M>
+where
+    M: Message,
+{
+    Monitor {
+        watcher: ActorId,
+        target: ActorId,
+    },
+    Demonitor {
+        watcher: ActorId,
+        target: ActorId,
+    },
+    Spawn {
+        parent: ActorId,
+        id: ActorId,
+        child: ActorObject<M>,
+        liveness: Arc<Liveness>,
+    },
+    DetachChild {
+        parent: ActorId,
+        child: ActorId,
+    },
+    Subscribe {
+        id: ActorId,
+        subscription_id: SubscriptionId,
+        subscription: Subscription<M>,
+    },
+    Unsubscribe {
+        id: ActorId,
+        subscription: Subscription<M>,
+    },
+    UnsubscribePredicate {
+        id: ActorId,
+        subscription_id: SubscriptionId,
+    },
+    SetFilter {
+        id: ActorId,
+        sig: M::MessageType,
+        filter: Filter<M>,
+    },
+    ClearFilter {
+        id: ActorId,
+        sig: M::MessageType,
+    },
+    /// Write a value to the store, or remove it with `None`.
+    Store {
+        key: KeyId,
+        value: Option<Value>,
+    },
+    Watch {
+        id: ActorId,
+        key: KeyId,
+    },
+    Unwatch {
+        id: ActorId,
+        key: KeyId,
+    },
+}
+
+impl<M> fmt::Debug for Command<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Command::Monitor { watcher, target } => f
+                .debug_struct("Monitor")
+                .field("watcher", watcher)
+                .field("target", target)
+                .finish(),
+            Command::Demonitor { watcher, target } => f
+                .debug_struct("Demonitor")
+                .field("watcher", watcher)
+                .field("target", target)
+                .finish(),
+            Command::Spawn { parent, id, .. } => f
+                .debug_struct("Spawn")
+                .field("parent", parent)
+                .field("id", id)
+                .finish_non_exhaustive(),
+            Command::DetachChild { parent, child } => f
+                .debug_struct("DetachChild")
+                .field("parent", parent)
+                .field("child", child)
+                .finish(),
+            Command::Subscribe {
+                id,
+                subscription_id,
+                subscription,
+            } => f
+                .debug_struct("Subscribe")
+                .field("id", id)
+                .field("subscription_id", subscription_id)
+                .field("subscription", subscription)
+                .finish(),
+            Command::Unsubscribe { id, subscription } => f
+                .debug_struct("Unsubscribe")
+                .field("id", id)
+                .field("subscription", subscription)
+                .finish(),
+            Command::UnsubscribePredicate {
+                id,
+                subscription_id,
+            } => f
+                .debug_struct("UnsubscribePredicate")
+                .field("id", id)
+                .field("subscription_id", subscription_id)
+                .finish(),
+            Command::SetFilter { id, sig, .. } => f
+                .debug_struct("SetFilter")
+                .field("id", id)
+                .field("sig", &sig.name())
+                .finish_non_exhaustive(),
+            Command::ClearFilter { id, sig } => f
+                .debug_struct("ClearFilter")
+                .field("id", id)
+                .field("sig", &sig.name())
+                .finish(),
+            Command::Store { key, value } => f
+                .debug_struct("Store")
+                .field("key", &key.name)
+                .field("remove", &value.is_none())
+                .finish(),
+            Command::Watch { id, key } => f
+                .debug_struct("Watch")
+                .field("id", id)
+                .field("key", &key.name)
+                .finish(),
+            Command::Unwatch { id, key } => f
+                .debug_struct("Unwatch")
+                .field("id", id)
+                .field("key", &key.name)
+                .finish(),
+        }
+    }
+}
+
+pub type Receiver<M> = mpsc::UnboundedReceiver<Packet<M>>;
+
+/// Sender that can be cloned and used to send envelopes and control commands
+/// to a commutator.
+pub struct Sender<M>
+where
+    M: Message,
+{
+    inner: mpsc::UnboundedSender<Packet<M>>,
+    registry: Arc<Registry>,
+    clock: Arc<Clock>,
+    store: Arc<Store>,
+    /// The number of packets that were sent and not received yet.
+    queued: Arc<AtomicUsize>,
+    /// The id of the next actor that is attached.
+    next_id: Arc<AtomicUsize>,
+    /// The span the envelopes that are sent outside of any span are linked
+    /// to, for senders that are handed to other tasks.
+    span: SendSpan,
+    /// The actor the sender was handed to when it was attached, which is the
+    /// parent of the actors that are spawned through the sender.
+    actor: Option<ActorId>,
+    /// Set while the envelopes that are sent through the sender are dropped,
+    /// such as while a persistent actor is rebuilt.
+    muted: Option<Arc<AtomicBool>>,
+}
+
+impl<M> Sender<M>
+where
+    M: Message,
+{
+    pub(crate) fn channel(
+        registry: Arc<Registry>,
+        clock: Arc<Clock>,
+        store: Arc<Store>,
+    ) -> (Self, Receiver<M>) {
+        let (inner, receiver) = mpsc::unbounded();
+        let sender = Self {
+            inner,
+            registry,
+            clock,
+            store,
+            queued: Arc::new(AtomicUsize::new(0)),
+            next_id: Arc::new(AtomicUsize::new(1)),
+            span: SendSpan::default(),
+            actor: None,
+            muted: None,
+        };
+        (sender, receiver)
+    }
+
+    /// Send an envelope to the commutator. Fails if the commutator was
+    /// dropped. With the `tracing` feature, the current span becomes the
+    /// parent of the span in which the envelope is dispatched. Outside of any
+    /// span, the span of the sender is used, such as the span in which a
+    /// `DeputyPublisher` was created.
+    pub fn unbounded_send(&self, envelope: Envelope<M>) -> Result<(), SendError<M>> {
+        if self.is_muted() {
+            return Ok(());
+        }
+        #[cfg(feature = "tracing")]
+        let envelope = {
+            let mut envelope = envelope;
+            if envelope.span.0.is_none() {
+                envelope.span = SendSpan::current();
+            }
+            if envelope.span.0.is_none() {
+                envelope.span = self.span.clone();
+            }
+            envelope
+        };
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .unbounded_send(Packet::Envelope(envelope))
+            .map_err(|error| match error.into_inner() {
+                Packet::Envelope(envelope) => {
+                    self.dequeued();
+                    SendError(envelope)
+                }
+                Packet::Control(_) => unreachable!(),
+            })
+    }
+
+    /// Let the actor this sender was handed to in `Actor::on_attach` be
+    /// notified with a `SystemMessage::Terminated` when the `target` actor is
+    /// detached. Nothing happens if the sender wasn't handed to an actor.
+    pub fn monitor(&self, target: ActorId) {
+        if let Some(watcher) = self.actor {
+            self.control(Command::Monitor { watcher, target });
+        }
+    }
+
+    /// Stop monitoring the `target` actor.
+    pub fn demonitor(&self, target: ActorId) {
+        if let Some(watcher) = self.actor {
+            self.control(Command::Demonitor { watcher, target });
+        }
+    }
+
+    /// Spawn a child actor of the actor this sender was handed to in
+    /// `Actor::on_attach`. The child is attached and initialized by the
+    /// commutator, and is detached automatically when the parent is detached.
+    /// If the parent is no longer attached by then, the child is dropped.
+    /// Returns `None` if the sender wasn't handed to an actor, such as the
+    /// sender of `Commutator::sender`, in which case the child is dropped.
+    ///
+    /// The returned handle can be used right away, since messages that are
+    /// posted to the child arrive after the child is attached. The handle is
+    /// no longer alive once the child is detached or dropped.
+    pub fn spawn(&self, child: ActorObject<M>) -> Option<ActorRef<M>> {
+        let parent = self.actor?;
+        let id = self.next_id();
+        let liveness = Liveness::new();
+        self.control(Command::Spawn {
+            parent,
+            id,
+            child,
+            liveness: liveness.clone(),
+        });
+        Some(ActorRef::new(id, self.unbound(), liveness))
+    }
+
+    /// Spawn a child actor that is created by a factory. The factory isn't
+    /// called if the sender wasn't handed to an actor.
+    pub fn spawn_from(&self, factory: &mut dyn ActorFactory<M>) -> Option<ActorRef<M>> {
+        self.actor?;
+        self.spawn(factory.create())
+    }
+
+    /// Detach a child actor that was spawned through this sender. The child
+    /// is deinitialized first. Nothing happens if the child isn't a child of
+    /// the actor this sender was handed to.
+    pub fn detach_child(&self, child: ActorId) {
+        if let Some(parent) = self.actor {
+            self.control(Command::DetachChild { parent, child });
+        }
+    }
+
+    /// Get a value that identifies the commutator of the sender, for as long
+    /// as the commutator exists.
+    pub(crate) fn commutator(&self) -> usize {
+        Arc::as_ptr(&self.registry) as usize
+    }
+
+    /// Get the id of the actor this sender was handed to in
+    /// `Actor::on_attach`, if any.
+    pub fn actor(&self) -> Option<ActorId> {
+        self.actor
+    }
+
+    /// Add a subscription for the actor this sender was handed to in
+    /// `Actor::on_attach`. The actor is notified through
+    /// `Actor::insert_subscription` once the commutator has added it. Returns
+    /// the id of the subscription, or `None` if the sender wasn't handed to
+    /// an actor.
+    pub fn subscribe(&self, subscription: Subscription<M>) -> Option<SubscriptionId> {
+        let id = self.actor?;
+        let subscription_id = SubscriptionId::next();
+        self.control(Command::Subscribe {
+            id,
+            subscription_id,
+            subscription,
+        });
+        Some(subscription_id)
+    }
+
+    /// Remove a subscription of the actor this sender was handed to. The
+    /// actor is notified through `Actor::remove_subscription` once the
+    /// commutator has removed it. Predicate subscriptions are removed with
+    /// `unsubscribe_predicate`. Nothing happens if the sender wasn't handed
+    /// to an actor.
+    pub fn unsubscribe(&self, subscription: Subscription<M>) {
+        if let Some(id) = self.actor {
+            self.control(Command::Unsubscribe { id, subscription });
+        }
+    }
+
+    /// Remove a predicate subscription of the actor this sender was handed
+    /// to by its id.
+    pub fn unsubscribe_predicate(&self, subscription_id: SubscriptionId) {
+        if let Some(id) = self.actor {
+            self.control(Command::UnsubscribePredicate {
+                id,
+                subscription_id,
+            });
+        }
+    }
+
+    /// Set the filter of the actor this sender was handed to for a message
+    /// type, replacing its current filter. Messages of that type are only
+    /// dispatched to the actor if the filter returns `true`.
+    pub fn set_filter(&self, sig: M::MessageType, filter: Filter<M>) {
+        if let Some(id) = self.actor {
+            self.control(Command::SetFilter { id, sig, filter });
+        }
+    }
+
+    /// Remove the filter of the actor this sender was handed to for a
+    /// message type.
+    pub fn clear_filter(&self, sig: M::MessageType) {
+        if let Some(id) = self.actor {
+            self.control(Command::ClearFilter { id, sig });
+        }
+    }
+
+    /// Get the id of the actor that is registered under the given name.
+    pub fn resolve(&self, name: &str) -> Option<ActorId> {
+        self.registry.resolve(name)
+    }
+
+    /// Get the registry of the commutator.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Get the state store of the commutator.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Write a value to the store. The value is written by the commutator,
+    /// which then notifies the actors that watch the key.
+    pub fn store_value<T>(&self, key: &Key<T>, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.control(Command::Store {
+            key: key.id(),
+            value: Some(Box::new(value)),
+        });
+    }
+
+    /// Remove a value from the store.
+    pub fn remove_value<T>(&self, key: &Key<T>)
+    where
+        T: 'static,
+    {
+        self.control(Command::Store {
+            key: key.id(),
+            value: None,
+        });
+    }
+
+    /// Let the actor this sender was handed to be notified with a
+    /// `SystemMessage::Changed` when the value of a key changes. Nothing
+    /// happens if the sender wasn't handed to an actor.
+    pub fn watch_key<T>(&self, key: &Key<T>)
+    where
+        T: 'static,
+    {
+        if let Some(id) = self.actor {
+            self.control(Command::Watch { id, key: key.id() });
+        }
+    }
+
+    /// Stop notifying the actor this sender was handed to about changes of a
+    /// key.
+    pub fn unwatch_key<T>(&self, key: &Key<T>)
+    where
+        T: 'static,
+    {
+        if let Some(id) = self.actor {
+            self.control(Command::Unwatch { id, key: key.id() });
+        }
+    }
+
+    /// Get the time according to the clock of the commutator. Actors should
+    /// use this time, so they behave the same way when a journal is replayed.
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Get the number of envelopes and control commands that were sent to
+    /// the commutator and not received yet.
+    pub fn queue_length(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Get the counter of the packets that were sent and not received yet.
+    pub(crate) fn queued(&self) -> Arc<AtomicUsize> {
+        self.queued.clone()
+    }
+
+    /// Get a sender that is handed to the actor with the given id.
+    pub(crate) fn for_actor(&self, id: ActorId) -> Self {
+        Self {
+            actor: Some(id),
+            ..self.clone()
+        }
+    }
+
+    /// Get a sender that isn't handed to any actor, and can't spawn.
+    fn unbound(&self) -> Self {
+        Self {
+            actor: None,
+            ..self.clone()
+        }
+    }
+
+    /// Get a sender whose envelopes are dropped while the flag is set.
+    /// Control commands are still sent.
+    pub(crate) fn muted_by(&self, muted: Arc<AtomicBool>) -> Self {
+        Self {
+            muted: Some(muted),
+            ..self.clone()
+        }
+    }
+
+    fn is_muted(&self) -> bool {
+        self.muted
+            .as_ref()
+            .is_some_and(|muted| muted.load(Ordering::Relaxed))
+    }
+
+    /// Link the envelopes that are sent outside of any span to the current
+    /// span.
+    pub(crate) fn in_current_span(mut self) -> Self {
+        self.span = SendSpan::current();
+        self
+    }
+
+    /// Get a new actor id.
+    pub(crate) fn next_id(&self) -> ActorId {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Count a packet that the commutator received.
+    pub(crate) fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Check if the commutator was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub(crate) fn control(&self, command: Command<M>) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        if let Err(error) = self.inner.unbounded_send(Packet::Control(Control(command))) {
+            self.dequeued();
+            if let Packet::Control(Control(command)) = error.into_inner() {
+                log::warn!("commutator is gone, dropping {:?}", command);
+            }
+        }
+    }
+}
+
+impl<M> Clone for Sender<M>
+where
+    M: Message,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            registry: self.registry.clone(),
+            clock: self.clock.clone(),
+            store: self.store.clone(),
+            queued: self.queued.clone(),
+            next_id: self.next_id.clone(),
+            span: self.span.clone(),
+            actor: self.actor,
+            muted: self.muted.clone(),
+        }
+    }
+}
+
+impl<M> fmt::Debug for Sender<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Sender")
+            .field("actor", &self.actor)
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// Error returned when an envelope couldn't be sent because the commutator
+/// was dropped.
+pub struct SendError<M>(pub Envelope<M>)
+where
+    M: Message;
+
+impl<M> SendError<M>
+where
+    M: Message,
+{
+    /// Get back the envelope that couldn't be sent.
+    pub fn into_inner(self) -> Envelope<M> {
+        self.0
+    }
+}
+
+impl<M> fmt::Debug for SendError<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<M> fmt::Display for SendError<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("commutator is gone")
+    }
+}
+
+impl<M> std::error::Error for SendError<M> where M: Message {}
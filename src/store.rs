@@ -1 +1,130 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::RwLock;
 
+/// Value in the store.
+pub(crate) type Value = Box<dyn Any + Send + Sync>;
+
+/// Typed key of a value in the store. Keys are usually declared as constants:
+///
+/// ```
+/// use armature::store::Key;
+///
+/// const VOLUME: Key<u8> = Key::new("volume");
+/// ```
+///
+/// A key is identified by its name and its type, so keys with the same name
+/// and different types have their own values.
+pub struct Key<T> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Key<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Key<T>
+where
+    T: 'static,
+{
+    pub(crate) fn id(&self) -> KeyId {
+        KeyId {
+            name: self.name,
+            type_id: TypeId::of::<T>(),
+        }
+    }
+}
+
+/// The name and the type of a key, which identify its value in the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct KeyId {
+    pub(crate) name: &'static str,
+    type_id: TypeId,
+}
+
+impl<T> Clone for Key<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Key<T> {}
+
+impl<T> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_tuple("Key").field(&self.name).finish()
+    }
+}
+
+/// State store of a commutator. The store is shared between the commutator
+/// and its senders, so actors can read the values. Values are written through
+/// the commutator, with `Sender::store_value`, and the actors that watch a key
+/// are notified with a `SystemMessage::Changed` when its value changes.
+#[derive(Default)]
+pub struct Store {
+    values: RwLock<HashMap<KeyId, Value>>,
+}
+
+impl Store {
+    /// Get a clone of a value. Returns `None` if there is no value for the
+    /// key.
+    pub fn get<T>(&self, key: &Key<T>) -> Option<T>
+    where
+        T: Clone + 'static,
+    {
+        self.with(key, T::clone)
+    }
+
+    /// Call a closure with a reference to a value, without cloning it.
+    pub fn with<T, R>(&self, key: &Key<T>, f: impl FnOnce(&T) -> R) -> Option<R>
+    where
+        T: 'static,
+    {
+        let values = self.values.read().unwrap();
+        values.get(&key.id())?.downcast_ref().map(f)
+    }
+
+    /// Check if there is a value for the key.
+    pub fn contains<T>(&self, key: &Key<T>) -> bool
+    where
+        T: 'static,
+    {
+        self.values.read().unwrap().contains_key(&key.id())
+    }
+
+    /// Get the names of the keys that have a value, sorted alphabetically.
+    pub fn keys(&self) -> Vec<String> {
+        let values = self.values.read().unwrap();
+        let mut keys: Vec<String> = values.keys().map(|key| key.name.to_string()).collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    pub(crate) fn set(&self, key: KeyId, value: Value) {
+        self.values.write().unwrap().insert(key, value);
+    }
+
+    /// Remove a value. Returns `false` if there was no value.
+    pub(crate) fn remove(&self, key: &KeyId) -> bool {
+        self.values.write().unwrap().remove(key).is_some()
+    }
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Store").field("keys", &self.keys()).finish()
+    }
+}
@@ -0,0 +1,363 @@
+use smallvec::SmallVec;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::actor::ActorId;
+use crate::message::*;
+
+/// Number of subscribers per message type that are stored inline before the
+/// subscription table has to allocate.
+const INLINE_SUBSCRIBERS: usize = 4;
+
+/// Predicate that selects the messages an actor is interested in.
+pub type Predicate<M> = fn(&M) -> bool;
+
+/// Filter on the content of the messages that are routed to a subscriber of
+/// a message type. The message is only handed to the subscriber if the filter
+/// returns `true`.
+pub type Filter<M> = Box<dyn Fn(&M) -> bool + Send>;
+
+/// A filter together with the message type it applies to.
+pub type SubscriptionFilter<M> = (<M as Message>::MessageType, Filter<M>);
+
+/// Identifier of a subscription, that is returned when the subscription is
+/// added. Predicates can't be compared, so a predicate subscription is
+/// removed by its id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+impl SubscriptionId {
+    /// Get a new subscription id, that is unique within the process.
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A subscription of an actor to the messages that are published to all
+/// actors.
+pub enum Subscription<M>
+where
+    M: Message,
+{
+    /// Subscribe to every message type, except the skipped ones.
+    All,
+    /// Subscribe to a single message type.
+    One(M::MessageType),
+    /// Subscribe to every message type in a named group.
+    Group(&'static str),
+    /// Subscribe to every message for which the predicate returns `true`.
+    Predicate(Predicate<M>),
+}
+
+impl<M> Subscription<M>
+where
+    M: Message,
+{
+    /// Get the message types that are covered by the subscription. Predicate
+    /// subscriptions don't cover any message type as a whole.
+    pub fn message_types(&self) -> Vec<M::MessageType> {
+        let all = <M::MessageType as MessageTypeInfo>::ALL
+            .iter()
+            .copied()
+            .filter(|message_type| !message_type.is_skipped());
+        match self {
+            Subscription::All => all.collect(),
+            Subscription::One(message_type) => all.filter(|t| t == message_type).collect(),
+            Subscription::Group(group) => all.filter(|t| t.in_group(group)).collect(),
+            Subscription::Predicate(_) => Vec::new(),
+        }
+    }
+}
+
+impl<M> Clone for Subscription<M>
+where
+    M: Message,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for Subscription<M> where M: Message {}
+
+impl<M> fmt::Debug for Subscription<M>
+where
+    M: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Subscription::All => f.write_str("All"),
+            Subscription::One(message_type) => {
+                f.debug_tuple("One").field(&message_type.name()).finish()
+            }
+            Subscription::Group(group) => f.debug_tuple("Group").field(group).finish(),
+            Subscription::Predicate(_) => f.write_str("Predicate"),
+        }
+    }
+}
+
+/// Subscription table that maps every message type to the actors that are
+/// subscribed to it. The table is a vector indexed by the dense index of the
+/// message type, so looking up the subscribers of a message doesn't require
+/// any hashing. Predicate subscriptions are kept separately and are evaluated
+/// for every routed message.
+///
+/// Subscriptions of the same actor can overlap, for example a subscription
+/// to a single message type and one to all message types. The table counts
+/// how many subscriptions of an actor cover a message type, and only drops
+/// the message type when the last of them is removed.
+///
+/// A subscriber can have a filter per message type, that is evaluated before
+/// a message of that type is routed to it.
+pub struct SubscriptionTable<T>
+where
+    T: MessageType,
+{
+    subscribers: Vec<SmallVec<[ActorId; INLINE_SUBSCRIBERS]>>,
+    counts: Vec<SmallVec<[usize; INLINE_SUBSCRIBERS]>>,
+    filters: Vec<Vec<(ActorId, Filter<T::Message>)>>,
+    predicates: Vec<(SubscriptionId, ActorId, Predicate<T::Message>)>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> SubscriptionTable<T>
+where
+    T: MessageType,
+{
+    pub fn new() -> Self {
+        Self {
+            subscribers: vec![SmallVec::new(); T::COUNT],
+            counts: vec![SmallVec::new(); T::COUNT],
+            filters: (0..T::COUNT).map(|_| Vec::new()).collect(),
+            predicates: Vec::new(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Subscribe an actor to a message type. Returns `false` if the actor was
+    /// already subscribed, in which case the message type is covered by one
+    /// more subscription of the actor.
+    pub fn insert(&mut self, message_type: T, id: ActorId) -> bool {
+        let index = message_type.index();
+        match self.position(message_type, id) {
+            Some(position) => {
+                self.counts[index][position] += 1;
+                false
+            }
+            None => {
+                self.subscribers[index].push(id);
+                self.counts[index].push(1);
+                true
+            }
+        }
+    }
+
+    /// Unsubscribe an actor from a message type, regardless of how many of
+    /// its subscriptions cover it. Returns `false` if the actor wasn't
+    /// subscribed.
+    pub fn remove(&mut self, message_type: T, id: ActorId) -> bool {
+        let index = message_type.index();
+        match self.position(message_type, id) {
+            Some(position) => {
+                self.subscribers[index].remove(position);
+                self.counts[index].remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop one of the subscriptions of an actor that cover a message type.
+    /// Returns `true` if it was the last one, and the actor is no longer
+    /// subscribed to the message type.
+    fn release(&mut self, message_type: T, id: ActorId) -> bool {
+        let index = message_type.index();
+        match self.position(message_type, id) {
+            Some(position) if self.counts[index][position] > 1 => {
+                self.counts[index][position] -= 1;
+                false
+            }
+            Some(_) => self.remove(message_type, id),
+            None => false,
+        }
+    }
+
+    fn position(&self, message_type: T, id: ActorId) -> Option<usize> {
+        self.subscribers[message_type.index()]
+            .iter()
+            .position(|subscriber| *subscriber == id)
+    }
+
+    /// Add a subscription for an actor. Returns the id of the subscription,
+    /// and the message types the actor wasn't subscribed to yet.
+    pub fn subscribe(
+        &mut self,
+        subscription: Subscription<T::Message>,
+        id: ActorId,
+    ) -> (SubscriptionId, Vec<T>) {
+        let subscription_id = SubscriptionId::next();
+        let added = self.subscribe_as(subscription_id, subscription, id);
+        (subscription_id, added)
+    }
+
+    /// Add a subscription for an actor under an id that was handed out
+    /// already. Returns the message types the actor wasn't subscribed to yet.
+    pub(crate) fn subscribe_as(
+        &mut self,
+        subscription_id: SubscriptionId,
+        subscription: Subscription<T::Message>,
+        id: ActorId,
+    ) -> Vec<T> {
+        if let Subscription::Predicate(predicate) = subscription {
+            self.predicates.push((subscription_id, id, predicate));
+        }
+        subscription
+            .message_types()
+            .into_iter()
+            .filter(|message_type| self.insert(*message_type, id))
+            .collect()
+    }
+
+    /// Remove a subscription of an actor. Returns the message types the actor
+    /// is no longer subscribed to, which leaves out the message types that
+    /// another subscription of the actor still covers. Predicate subscriptions
+    /// are not removed, use `unsubscribe_predicate` with their id instead.
+    pub fn unsubscribe(&mut self, subscription: Subscription<T::Message>, id: ActorId) -> Vec<T> {
+        subscription
+            .message_types()
+            .into_iter()
+            .filter(|message_type| self.release(*message_type, id))
+            .collect()
+    }
+
+    /// Remove a predicate subscription of an actor. Returns `false` if the
+    /// actor has no predicate subscription with the given id.
+    pub fn unsubscribe_predicate(&mut self, subscription_id: SubscriptionId, id: ActorId) -> bool {
+        let len = self.predicates.len();
+        self.predicates
+            .retain(|(other, subscriber, _)| *other != subscription_id || *subscriber != id);
+        self.predicates.len() != len
+    }
+
+    /// Unsubscribe an actor from all message types and remove its predicate
+    /// subscriptions.
+    pub fn remove_actor(&mut self, id: ActorId) {
+        for message_type in T::ALL.iter().copied() {
+            self.remove(message_type, id);
+        }
+        for filters in self.filters.iter_mut() {
+            filters.retain(|(subscriber, _)| *subscriber != id);
+        }
+        self.predicates
+            .retain(|(_, subscriber, _)| *subscriber != id);
+    }
+
+    /// Set the filter of an actor for a message type, replacing any previous
+    /// filter. The filter is kept when the actor unsubscribes from the message
+    /// type and subscribes again.
+    pub fn set_filter(&mut self, message_type: T, id: ActorId, filter: Filter<T::Message>) {
+        let filters = &mut self.filters[message_type.index()];
+        match filters.iter_mut().find(|(subscriber, _)| *subscriber == id) {
+            Some((_, existing)) => *existing = filter,
+            None => filters.push((id, filter)),
+        }
+    }
+
+    /// Remove the filter of an actor for a message type. Returns `false` if
+    /// the actor had no filter for the message type.
+    pub fn clear_filter(&mut self, message_type: T, id: ActorId) -> bool {
+        let filters = &mut self.filters[message_type.index()];
+        let len = filters.len();
+        filters.retain(|(subscriber, _)| *subscriber != id);
+        filters.len() != len
+    }
+
+    /// Check if an actor has a filter for a message type.
+    pub fn has_filter(&self, message_type: T, id: ActorId) -> bool {
+        self.filters[message_type.index()]
+            .iter()
+            .any(|(subscriber, _)| *subscriber == id)
+    }
+
+    /// Get the actors a message should be routed to. The subscribers of the
+    /// message type come first, in subscription order, followed by the actors
+    /// with a matching predicate subscription, also in subscription order.
+    /// Subscribers whose filter rejects the message are left out. Every actor
+    /// is listed at most once.
+    pub fn route(&self, message: &T::Message) -> SmallVec<[ActorId; INLINE_SUBSCRIBERS]> {
+        let message_type = T::from(message);
+        if message_type.is_skipped() {
+            return SmallVec::new();
+        }
+        let subscribers = self.subscribers(message_type);
+        let filters = &self.filters[message_type.index()];
+        let mut route: SmallVec<[ActorId; INLINE_SUBSCRIBERS]> = if filters.is_empty() {
+            SmallVec::from(subscribers)
+        } else {
+            subscribers
+                .iter()
+                .copied()
+                .filter(|id| {
+                    filters
+                        .iter()
+                        .find(|(subscriber, _)| subscriber == id)
+                        .is_none_or(|(_, filter)| filter(message))
+                })
+                .collect()
+        };
+        for (_, id, predicate) in self.predicates.iter() {
+            if !route.contains(id) && predicate(message) {
+                route.push(*id);
+            }
+        }
+        route
+    }
+
+    /// Get the actors that are subscribed to a message type.
+    pub fn subscribers(&self, message_type: T) -> &[ActorId] {
+        &self.subscribers[message_type.index()]
+    }
+
+    /// Check if an actor is subscribed to a message type.
+    pub fn is_subscribed(&self, message_type: T, id: ActorId) -> bool {
+        self.subscribers(message_type).contains(&id)
+    }
+
+    /// Get the number of predicate subscriptions of an actor.
+    pub fn predicate_count(&self, id: ActorId) -> usize {
+        self.predicates
+            .iter()
+            .filter(|(_, subscriber, _)| *subscriber == id)
+            .count()
+    }
+
+    /// Get the message types an actor is subscribed to.
+    pub fn subscriptions(&self, id: ActorId) -> Vec<T> {
+        T::ALL
+            .iter()
+            .copied()
+            .filter(|message_type| self.is_subscribed(*message_type, id))
+            .collect()
+    }
+}
+
+impl<T> Default for SubscriptionTable<T>
+where
+    T: MessageType,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for SubscriptionTable<T>
+where
+    T: MessageType + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_map()
+            .entries(T::ALL.iter().zip(self.subscribers.iter()))
+            .finish()
+    }
+}
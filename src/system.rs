@@ -0,0 +1,44 @@
+use crate::actor::ActorId;
+use crate::message::{Message, MessageType};
+use armature_macro::MessageType;
+
+/// Notifications the commutator sends to actors about the system itself. They
+/// are delivered to `Actor::handle_system` with `Origin::System`.
+#[derive(Clone, Debug, PartialEq, Eq, MessageType)]
+#[message_type(name = "SystemMessageType")]
+#[cfg_attr(feature = "serde", message_type(serde))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SystemMessage {
+    /// A monitored actor is gone.
+    Terminated {
+        id: ActorId,
+        reason: TerminationReason,
+    },
+    /// The value of a watched key of the store was written or removed.
+    Changed { key: String },
+}
+
+impl Message for SystemMessage {
+    type MessageType = SystemMessageType;
+}
+
+impl MessageType for SystemMessageType {
+    type Message = SystemMessage;
+}
+
+/// Why a monitored actor is gone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum TerminationReason {
+    /// The actor was detached from the commutator.
+    Detached,
+    /// The actor panicked while handling a message, and was detached.
+    Panicked(String),
+    /// The commutator shut down: `run` stopped, or the commutator was
+    /// dropped.
+    Shutdown,
+    /// The actor wasn't attached when the monitor was set up.
+    NotAttached,
+}
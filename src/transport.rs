@@ -0,0 +1,447 @@
+use async_std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::task;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures::stream::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::actor::{Actor, ActorId};
+use crate::message::*;
+use crate::Sender;
+
+/// Largest frame that is accepted from a peer.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Default delay between two attempts to connect to a peer.
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// Address of the peer a transport connects to.
+#[derive(Clone, Debug)]
+pub enum Endpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+enum Role {
+    Connect(Endpoint),
+    ListenTcp(TcpListener),
+    #[cfg(unix)]
+    ListenUnix(UnixListener),
+}
+
+/// Actor that links the commutator it is attached to with a commutator in
+/// another process, over a TCP stream or a Unix domain socket.
+///
+/// The transport subscribes to the given message types and sends every
+/// message it receives to the peer, which publishes it to all its actors.
+/// Envelopes are sent as frames of JSON, prefixed with their length as a
+/// big-endian `u32`.
+///
+/// Actor ids are only meaningful within their own commutator. For every
+/// remote actor that sends a message, the transport spawns a local proxy
+/// actor as its child, and replaces the origin of the message with the id of
+/// the proxy. Messages that are posted to the proxy are sent to the remote
+/// actor. Messages from anonymous remote publishers get the transport itself
+/// as origin. Messages that came from the peer are never sent back to it.
+///
+/// A transport that connects to its peer keeps trying to reconnect when the
+/// connection is lost, and a transport that listens accepts a new connection.
+/// Messages that are sent while the peer is disconnected are queued, but a
+/// message that was being written when the connection was lost may be lost.
+/// The proxies are detached when the connection is lost, since a peer that
+/// restarts gives its actors new ids.
+pub struct Transport<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    role: Option<Role>,
+    message_types: Vec<M::MessageType>,
+    reconnect_delay: Duration,
+    local_addr: Option<SocketAddr>,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    outbound_receiver: Option<mpsc::UnboundedReceiver<Vec<u8>>>,
+    proxies: Arc<Mutex<Proxies>>,
+    connected: Arc<AtomicBool>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+/// The local proxies of the remote actors.
+#[derive(Default)]
+struct Proxies {
+    by_remote: HashMap<ActorId, ActorId>,
+    local: HashSet<ActorId>,
+}
+
+impl<M> Transport<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    fn new(role: Role, message_types: Vec<M::MessageType>) -> Self {
+        let (outbound, outbound_receiver) = mpsc::unbounded();
+        Self {
+            role: Some(role),
+            message_types,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            local_addr: None,
+            outbound,
+            outbound_receiver: Some(outbound_receiver),
+            proxies: Arc::new(Mutex::new(Proxies::default())),
+            connected: Arc::new(AtomicBool::new(false)),
+            shutdown: None,
+        }
+    }
+
+    /// Create a transport that connects to a peer that listens on the given
+    /// TCP address.
+    pub fn connect_tcp(addr: impl Into<String>, message_types: Vec<M::MessageType>) -> Self {
+        Self::new(Role::Connect(Endpoint::Tcp(addr.into())), message_types)
+    }
+
+    /// Create a transport that connects to a peer that listens on the given
+    /// Unix domain socket.
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl Into<PathBuf>, message_types: Vec<M::MessageType>) -> Self {
+        Self::new(Role::Connect(Endpoint::Unix(path.into())), message_types)
+    }
+
+    /// Create a transport that listens on the given TCP address for a peer
+    /// to connect.
+    pub async fn bind_tcp(
+        addr: impl ToSocketAddrs,
+        message_types: Vec<M::MessageType>,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let mut transport = Self::new(Role::ListenTcp(listener), message_types);
+        transport.local_addr = Some(local_addr);
+        Ok(transport)
+    }
+
+    /// Create a transport that listens on the given Unix domain socket for a
+    /// peer to connect.
+    #[cfg(unix)]
+    pub async fn bind_unix(
+        path: impl Into<PathBuf>,
+        message_types: Vec<M::MessageType>,
+    ) -> io::Result<Self> {
+        let listener = UnixListener::bind(path.into()).await?;
+        Ok(Self::new(Role::ListenUnix(listener), message_types))
+    }
+
+    /// Set the delay between two attempts to connect to the peer.
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Get the address a TCP transport listens on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Check if the transport is connected to its peer.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    /// Get the id of the local proxy of a remote actor, if the remote actor
+    /// has sent a message.
+    pub fn proxy(&self, remote: ActorId) -> Option<ActorId> {
+        self.proxies.lock().unwrap().by_remote.get(&remote).copied()
+    }
+
+    fn is_remote(&self, origin: Origin) -> bool {
+        match origin {
+            Origin::Actor(id) => {
+                Some(id) == self.id() || self.proxies.lock().unwrap().local.contains(&id)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<M> Actor for Transport<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    type Message = M;
+
+    fn default_subscriptions(&self) -> Vec<M::MessageType> {
+        self.message_types.clone()
+    }
+
+    fn on_attach(&mut self, sender: &Sender<M>) {
+        let id = match self.id() {
+            Some(id) => id,
+            None => return,
+        };
+        let (role, outbound_receiver) = match (self.role.take(), self.outbound_receiver.take()) {
+            (Some(role), Some(outbound_receiver)) => (role, outbound_receiver),
+            _ => {
+                log::error!("transport {} can only be attached once", id);
+                return;
+            }
+        };
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        self.shutdown = Some(shutdown);
+        let link = Link {
+            id,
+            sender: sender.clone(),
+            outbound: self.outbound.clone(),
+            proxies: self.proxies.clone(),
+            connected: self.connected.clone(),
+            reconnect_delay: self.reconnect_delay,
+        };
+        task::spawn(async move {
+            // Dropping the transport cancels the shutdown receiver, which
+            // drops the link and closes the connection.
+            let run = link.run(role, outbound_receiver);
+            future::select(Box::pin(run), shutdown_receiver).await;
+        });
+    }
+
+    fn on_detach(&mut self) {
+        self.shutdown.take();
+        self.connected.store(false, Ordering::Release);
+    }
+
+    fn handle(&mut self, envelope: &Envelope<M>) {
+        if self.is_remote(envelope.origin) {
+            return;
+        }
+        if let Some(frame) = encode(envelope.origin, Destination::All, &envelope.message) {
+            let _ = self.outbound.unbounded_send(frame);
+        }
+    }
+}
+
+impl<M> fmt::Debug for Transport<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Transport")
+            .field("connected", &self.is_connected())
+            .field("local_addr", &self.local_addr)
+            .finish()
+    }
+}
+
+/// Local stand-in for an actor of the peer.
+struct Proxy<M> {
+    remote: ActorId,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Actor for Proxy<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    type Message = M;
+
+    fn handle(&mut self, envelope: &Envelope<M>) {
+        let destination = Destination::Single(self.remote);
+        if let Some(frame) = encode(envelope.origin, destination, &envelope.message) {
+            let _ = self.outbound.unbounded_send(frame);
+        }
+    }
+}
+
+/// The state of the task that maintains the connection to the peer.
+struct Link<M>
+where
+    M: Message,
+{
+    id: ActorId,
+    sender: Sender<M>,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    proxies: Arc<Mutex<Proxies>>,
+    connected: Arc<AtomicBool>,
+    reconnect_delay: Duration,
+}
+
+impl<M> Link<M>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    async fn run(self, role: Role, mut outbound: mpsc::UnboundedReceiver<Vec<u8>>) {
+        loop {
+            let result = match &role {
+                Role::Connect(Endpoint::Tcp(addr)) => match TcpStream::connect(addr.as_str()).await
+                {
+                    Ok(stream) => self.serve(stream, &mut outbound).await,
+                    Err(error) => Err(error),
+                },
+                #[cfg(unix)]
+                Role::Connect(Endpoint::Unix(path)) => match UnixStream::connect(path).await {
+                    Ok(stream) => self.serve(stream, &mut outbound).await,
+                    Err(error) => Err(error),
+                },
+                Role::ListenTcp(listener) => match listener.accept().await {
+                    Ok((stream, _)) => self.serve(stream, &mut outbound).await,
+                    Err(error) => Err(error),
+                },
+                #[cfg(unix)]
+                Role::ListenUnix(listener) => match listener.accept().await {
+                    Ok((stream, _)) => self.serve(stream, &mut outbound).await,
+                    Err(error) => Err(error),
+                },
+            };
+            match result {
+                Ok(()) => return,
+                Err(error) => log::info!("transport {} is disconnected: {}", self.id, error),
+            }
+            self.clear_proxies();
+            if let Role::Connect(_) = role {
+                task::sleep(self.reconnect_delay).await;
+            }
+        }
+    }
+
+    /// Exchange frames with the peer until the connection is lost, which is
+    /// returned as an error, or until the commutator is gone.
+    async fn serve<S>(
+        &self,
+        stream: S,
+        outbound: &mut mpsc::UnboundedReceiver<Vec<u8>>,
+    ) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Clone + Unpin,
+    {
+        let mut reader = stream.clone();
+        let mut writer = stream;
+        self.connected.store(true, Ordering::Release);
+        log::info!("transport {} is connected", self.id);
+        let read = async {
+            loop {
+                let frame = read_frame(&mut reader).await?;
+                if !self.deliver(&frame) {
+                    return Ok(());
+                }
+            }
+        };
+        let write = async {
+            while let Some(frame) = outbound.next().await {
+                writer.write_all(&frame).await?;
+            }
+            Ok(())
+        };
+        let result = match future::select(Box::pin(read), Box::pin(write)).await {
+            Either::Left((result, _)) | Either::Right((result, _)) => result,
+        };
+        self.connected.store(false, Ordering::Release);
+        result
+    }
+
+    /// Publish a frame that was received from the peer to the local
+    /// commutator. Returns `false` if the commutator is gone.
+    fn deliver(&self, frame: &[u8]) -> bool {
+        let envelope: Envelope<M> = match serde_json::from_slice(frame) {
+            Ok(envelope) => envelope,
+            Err(error) => {
+                log::warn!("transport {} dropped a malformed frame: {}", self.id, error);
+                return true;
+            }
+        };
+        let origin = match envelope.origin {
+            Origin::Actor(remote) => Origin::Actor(self.proxy(remote)),
+            _ => Origin::Actor(self.id),
+        };
+        let envelope = Envelope::new(origin, envelope.destination, envelope.message);
+        self.sender.unbounded_send(envelope).is_ok()
+    }
+
+    /// Detach the proxies of the remote actors of the lost connection.
+    fn clear_proxies(&self) {
+        let mut proxies = self.proxies.lock().unwrap();
+        proxies.by_remote.clear();
+        for proxy in proxies.local.drain() {
+            self.sender.detach_child(proxy);
+        }
+    }
+
+    /// Get the local proxy of a remote actor, and spawn it if it doesn't
+    /// exist yet.
+    fn proxy(&self, remote: ActorId) -> ActorId {
+        let mut proxies = self.proxies.lock().unwrap();
+        if let Some(id) = proxies.by_remote.get(&remote) {
+            return *id;
+        }
+        let proxy = Proxy::<M> {
+            remote,
+            outbound: self.outbound.clone(),
+            _marker: PhantomData,
+        };
+        let id = match self.sender.spawn(Box::new(proxy)) {
+            Some(proxy) => proxy.id(),
+            None => return self.id,
+        };
+        proxies.by_remote.insert(remote, id);
+        proxies.local.insert(id);
+        id
+    }
+}
+
+/// An envelope as it is written to the peer. It has the same layout as a
+/// serialized `Envelope`, but borrows the message.
+#[derive(Serialize)]
+struct Frame<'a, M> {
+    origin: Origin,
+    destination: Destination,
+    message: &'a M,
+}
+
+fn encode<M>(origin: Origin, destination: Destination, message: &M) -> Option<Vec<u8>>
+where
+    M: Serialize,
+{
+    let frame = Frame {
+        origin,
+        destination,
+        message,
+    };
+    let mut bytes = vec![0; 4];
+    if let Err(error) = serde_json::to_writer(&mut bytes, &frame) {
+        log::error!("can't serialize message: {}", error);
+        return None;
+    }
+    let len = bytes.len() - 4;
+    if len > MAX_FRAME_LEN {
+        log::error!("message of {} bytes is too large to send", len);
+        return None;
+    }
+    bytes[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    Some(bytes)
+}
+
+async fn read_frame<R>(reader: &mut R) -> io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len = [0; 4];
+    reader.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
+}
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::actor::Actor;
+use crate::message::*;
+use crate::subscription::Subscription;
+use crate::system::SystemMessage;
+use crate::Sender;
+
+/// Actor that only accepts a subset of the messages of a commutator, as its
+/// own local message type. The messages that are dispatched to the actor are
+/// converted into the local type with `TryFrom`, and the messages that can't
+/// be converted are dropped.
+///
+/// A typed actor is attached with `Commutator::attach_typed`, which wraps it
+/// in a `Typed` adapter.
+pub trait TypedActor: Send + Sized + 'static {
+    /// The message type of the commutator.
+    type Message: Message;
+    /// The messages the actor accepts.
+    type Local: for<'a> TryFrom<&'a Self::Message>;
+
+    /// Handle a message that was converted into the local message type. The
+    /// envelope of the original message carries its origin and reply
+    /// address.
+    fn handle(&mut self, message: Self::Local, envelope: &Envelope<Self::Message>);
+
+    /// Handle a notification from the commutator.
+    fn handle_system(&mut self, _: &Envelope<SystemMessage>) {}
+
+    /// Lifecycle method that is called when the actor is attached.
+    fn on_attach(&mut self, _: &Sender<Self::Message>) {}
+
+    /// Lifecycle method that is called when the actor is detached.
+    fn on_detach(&mut self) {}
+
+    /// Init method that is called when the commutator starts running.
+    fn init(&mut self) {}
+
+    /// Deinit method that is called before the actor is detached.
+    fn deinit(&mut self) {}
+
+    /// Get the initial subscriptions of the actor. By default the actor
+    /// receives every broadcast message that converts into its local message
+    /// type, which is checked for every published message. Actors can list
+    /// the message types they accept instead.
+    fn initial_subscriptions(&self) -> Vec<Subscription<Self::Message>> {
+        vec![Subscription::Predicate(accepts::<Self>)]
+    }
+
+    /// Get the groups the actor joins when it is attached.
+    fn default_groups(&self) -> Vec<GroupId> {
+        Vec::new()
+    }
+}
+
+fn accepts<A>(message: &A::Message) -> bool
+where
+    A: TypedActor,
+{
+    A::Local::try_from(message).is_ok()
+}
+
+/// Adapter that attaches a typed actor to a commutator.
+pub struct Typed<A>(A)
+where
+    A: TypedActor;
+
+impl<A> Typed<A>
+where
+    A: TypedActor,
+{
+    pub fn new(actor: A) -> Self {
+        Self(actor)
+    }
+
+    /// Get the typed actor.
+    pub fn get_ref(&self) -> &A {
+        &self.0
+    }
+
+    /// Get the typed actor mutably.
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.0
+    }
+
+    /// Unwrap the typed actor.
+    pub fn into_inner(self) -> A {
+        self.0
+    }
+}
+
+impl<A> Actor for Typed<A>
+where
+    A: TypedActor,
+{
+    type Message = A::Message;
+
+    fn handle(&mut self, envelope: &Envelope<Self::Message>) {
+        match A::Local::try_from(&envelope.message) {
+            Ok(message) => self.0.handle(message, envelope),
+            Err(_) => log::debug!(
+                "{} doesn't accept {}",
+                std::any::type_name::<A>(),
+                <Self::Message as Message>::MessageType::from(&envelope.message).name()
+            ),
+        }
+    }
+
+    fn handle_system(&mut self, envelope: &Envelope<SystemMessage>) {
+        self.0.handle_system(envelope);
+    }
+
+    fn on_attach(&mut self, sender: &Sender<Self::Message>) {
+        self.0.on_attach(sender);
+    }
+
+    fn on_detach(&mut self) {
+        self.0.on_detach();
+    }
+
+    fn init(&mut self) {
+        self.0.init();
+    }
+
+    fn deinit(&mut self) {
+        self.0.deinit();
+    }
+
+    fn initial_subscriptions(&self) -> Vec<Subscription<Self::Message>> {
+        self.0.initial_subscriptions()
+    }
+
+    fn default_groups(&self) -> Vec<GroupId> {
+        self.0.default_groups()
+    }
+}
+
+impl<A> fmt::Debug for Typed<A>
+where
+    A: TypedActor + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_tuple("Typed").field(&self.0).finish()
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::actor::ActorId;
+
+/// Heartbeat of the loop of a commutator, that tells since when the loop is
+/// busy with a packet, and which actor it is calling.
+pub(crate) struct Heartbeat {
+    start: Instant,
+    /// The time since `start` at which the loop started to process the
+    /// current packet, in nanoseconds plus one, or zero while it is idle.
+    busy_since: AtomicU64,
+    /// The number of packets the loop started to process.
+    beats: AtomicU64,
+    /// The id of the actor that is being called, or zero.
+    actor: AtomicUsize,
+}
+
+impl Heartbeat {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            busy_since: AtomicU64::new(0),
+            beats: AtomicU64::new(0),
+            actor: AtomicUsize::new(0),
+        }
+    }
+
+    /// The loop starts to process a packet.
+    pub(crate) fn busy(&self) {
+        let now = self.start.elapsed().as_nanos() as u64 + 1;
+        self.busy_since.store(now, Ordering::Release);
+        self.beats.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// The loop is done with the packet.
+    pub(crate) fn idle(&self) {
+        self.busy_since.store(0, Ordering::Release);
+    }
+
+    /// The loop calls an actor, or is done calling it with `None`.
+    pub(crate) fn calling(&self, id: Option<ActorId>) {
+        self.actor.store(id.unwrap_or(0), Ordering::Release);
+    }
+
+    /// Get the number of packets the loop started to process.
+    fn beats(&self) -> u64 {
+        self.beats.load(Ordering::Acquire)
+    }
+
+    /// Get the number of the packet the loop is stuck on, how long it is
+    /// busy with it and the actor it is calling, if it is busy.
+    fn check(&self) -> Option<(u64, Duration, Option<ActorId>)> {
+        let busy_since = self.busy_since.load(Ordering::Acquire);
+        if busy_since == 0 {
+            return None;
+        }
+        let beat = self.beats.load(Ordering::Acquire);
+        let since = Duration::from_nanos(busy_since - 1);
+        let actor = match self.actor.load(Ordering::Acquire) {
+            0 => None,
+            id => Some(id),
+        };
+        Some((beat, self.start.elapsed().saturating_sub(since), actor))
+    }
+}
+
+/// Report of a commutator loop that didn't make progress.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stall {
+    /// How long the loop has been busy with the same packet, or how long
+    /// packets have been waiting while the loop didn't take any, when the
+    /// stall was detected.
+    pub duration: Duration,
+    /// The actor whose handler was running, if any.
+    pub actor: Option<ActorId>,
+}
+
+/// Watchdog that detects when the loop of a commutator hasn't made progress
+/// for a given duration, either because it is stuck on a single packet, or
+/// because packets are waiting in the queue and the loop doesn't take them,
+/// for example because it isn't polled. Every stall is logged and reported
+/// once. The watchdog runs on its own thread, so it also works when a
+/// handler blocks the executor, and it stops when it is dropped.
+pub struct Watchdog {
+    /// Dropping the sender wakes the thread and stops it.
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn spawn(
+        heartbeat: Arc<Heartbeat>,
+        queued: Arc<AtomicUsize>,
+        limit: Duration,
+        mut report: impl FnMut(Stall) + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let interval = (limit / 4).max(Duration::from_millis(1));
+        let thread = thread::spawn(move || {
+            let mut reported = None;
+            // The beat at which packets were found waiting, and since when.
+            let mut waiting: Option<(u64, Instant)> = None;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let (beat, duration, actor) = match heartbeat.check() {
+                    Some(check) => {
+                        waiting = None;
+                        check
+                    }
+                    None if queued.load(Ordering::Relaxed) > 0 => {
+                        let beat = heartbeat.beats();
+                        let since = match waiting {
+                            Some((waiting_beat, since)) if waiting_beat == beat => since,
+                            _ => waiting.insert((beat, Instant::now())).1,
+                        };
+                        (beat, since.elapsed(), None)
+                    }
+                    None => {
+                        waiting = None;
+                        continue;
+                    }
+                };
+                let busy = waiting.is_none();
+                if duration < limit || reported == Some((beat, busy)) {
+                    continue;
+                }
+                reported = Some((beat, busy));
+                match actor {
+                    Some(id) => log::error!(
+                        "commutator stalled for {:?} in the handler of actor {}",
+                        duration,
+                        id
+                    ),
+                    None if busy => log::error!("commutator stalled for {:?}", duration),
+                    None => log::error!("commutator didn't take queued packets for {:?}", duration),
+                }
+                report(Stall { duration, actor });
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
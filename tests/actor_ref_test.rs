@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, ActorId, Commutator, Envelope, MessageType};
+    use async_std::task::block_on;
+
+    #[derive(Debug, PartialEq, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Question(u32),
+        Answer(u32),
+        Ignore,
+        Detach(ActorId),
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[derive(Default)]
+    struct Oracle {
+        _questions: usize,
+    }
+
+    impl Actor for Oracle {
+        type Message = Event;
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Question(question) = envelope.message {
+                assert!(envelope.expects_reply());
+                envelope.reply(Event::Answer(question * 2)).unwrap();
+                assert!(!envelope.expects_reply());
+            }
+        }
+    }
+
+    #[test]
+    fn actor_ref_ask_and_watch() {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|commutator, message| match message {
+            Event::Detach(id) => {
+                commutator.detach(id);
+                if commutator.handlers().is_empty() {
+                    InterceptResult::Break
+                } else {
+                    InterceptResult::Interception
+                }
+            }
+            message => InterceptResult::Pass(message),
+        });
+        let oracle = commutator.attach(Box::new(Oracle::default()));
+        assert!(oracle.is_alive());
+        assert_eq!(commutator.actor_ref(oracle.id()), Some(oracle.clone()));
+
+        let watcher = oracle.clone();
+        let (_, (answer, unanswered)) = block_on(async {
+            futures::join!(commutator.run(), async {
+                let answer = oracle.ask(Event::Question(21)).await;
+                let unanswered = oracle.ask(Event::Ignore).await;
+                oracle.post(Event::Detach(oracle.id())).unwrap();
+                watcher.watch().await;
+                (answer, unanswered)
+            })
+        });
+        assert_eq!(answer, Ok(Event::Answer(42)));
+        assert!(unanswered.is_err());
+        assert!(!oracle.is_alive());
+        assert_eq!(oracle.post(Event::Ignore), Err(Event::Ignore));
+        assert!(commutator.actor_ref(oracle.id()).is_none());
+    }
+
+    struct Zst;
+
+    impl Actor for Zst {
+        type Message = Event;
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    #[test]
+    fn zero_sized_actors_get_their_own_ids() {
+        let mut commutator = Commutator::new();
+        let first = commutator.attach(Box::new(Zst));
+        let second = commutator.attach_and_init(Box::new(Zst));
+        assert_ne!(first.id(), second.id());
+        assert_eq!(commutator.actor_ids(), &[first.id(), second.id()]);
+
+        assert!(commutator.detach(first.id()).is_some());
+        assert!(!first.is_alive());
+        assert!(second.is_alive());
+        assert_eq!(commutator.actor_ids(), &[second.id()]);
+    }
+}
@@ -0,0 +1,400 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::publisher::Publisher;
+    use armature::{Actor, Bridge, Commutator, Destination, Envelope, MessageType, Origin};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "UiSignal")]
+    pub enum UiEvent {
+        Click(u32),
+        Hover,
+        /// The controller executed a command.
+        Done(u32),
+        Stop,
+    }
+
+    impl armature::Message for UiEvent {
+        type MessageType = UiSignal;
+    }
+
+    impl armature::MessageType for UiSignal {
+        type Message = UiEvent;
+    }
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "ControlSignal")]
+    pub enum ControlEvent {
+        Command(u32),
+        Ack(u32),
+        Stop,
+    }
+
+    impl armature::Message for ControlEvent {
+        type MessageType = ControlSignal;
+    }
+
+    impl armature::MessageType for ControlSignal {
+        type Message = ControlEvent;
+    }
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "StorageSignal")]
+    pub enum StorageEvent {
+        Save(u32),
+        Stop,
+    }
+
+    impl armature::Message for StorageEvent {
+        type MessageType = StorageSignal;
+    }
+
+    impl armature::MessageType for StorageSignal {
+        type Message = StorageEvent;
+    }
+
+    /// Commands received by the controller, with their origin.
+    type Log = Arc<Mutex<Vec<(u32, Origin)>>>;
+
+    struct Controller {
+        log: Log,
+    }
+
+    impl Actor for Controller {
+        type Message = ControlEvent;
+
+        fn default_subscriptions(&self) -> Vec<ControlSignal> {
+            vec![ControlSignal::Command]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<ControlEvent>) {
+            if let ControlEvent::Command(command) = envelope.message {
+                self.log.lock().unwrap().push((command, envelope.origin));
+                if envelope.expects_reply() {
+                    envelope.reply(ControlEvent::Ack(command)).unwrap();
+                }
+            }
+        }
+    }
+
+    fn bridge(control: &Commutator<ControlEvent>) -> Bridge<UiEvent, ControlEvent> {
+        Bridge::new(
+            control.sender(),
+            vec![UiSignal::Click, UiSignal::Hover],
+            |event: &UiEvent| match event {
+                UiEvent::Click(button) => Some(ControlEvent::Command(*button)),
+                _ => None,
+            },
+        )
+    }
+
+    #[test]
+    fn forward_between_commutators() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut control = Commutator::new();
+        control.set_interceptor(|_, message| match message {
+            ControlEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        control.attach(Box::new(Controller { log: log.clone() }));
+
+        let mut ui = Commutator::new();
+        ui.set_interceptor(|_, message| match message {
+            UiEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let bridge = ui.attach(Box::new(bridge(&control))).id();
+
+        ui.sender().send(Envelope::new(
+            Origin::Actor(42),
+            Destination::All,
+            UiEvent::Click(1),
+        ));
+        ui.publish(UiEvent::Hover);
+        ui.publish(UiEvent::Click(2));
+        ui.publish(UiEvent::Stop);
+        block_on(ui.run());
+
+        control.publish(ControlEvent::Stop);
+        block_on(control.run());
+
+        let bridged = |actor| Origin::Bridged { bridge, actor };
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![(1, bridged(Some(42))), (2, bridged(None))]
+        );
+    }
+
+    /// Clicks that were confirmed by the controller.
+    struct Button {
+        done: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Actor for Button {
+        type Message = UiEvent;
+
+        fn handle(&mut self, envelope: &Envelope<UiEvent>) {
+            if let UiEvent::Done(button) = envelope.message {
+                self.done.lock().unwrap().push(button);
+            }
+        }
+    }
+
+    #[test]
+    fn reply_through_bridge() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut control = Commutator::new();
+        control.set_interceptor(|_, message| match message {
+            ControlEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        control.attach(Box::new(Controller { log: log.clone() }));
+
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let mut ui = Commutator::new();
+        ui.set_interceptor(|_, message| match message {
+            UiEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let button = ui.attach(Box::new(Button { done: done.clone() }));
+        let bridge = bridge(&control).with_replies(|event: &ControlEvent| match event {
+            ControlEvent::Ack(button) => Some(UiEvent::Done(*button)),
+            _ => None,
+        });
+        let bridge = ui.attach(Box::new(bridge));
+
+        ui.sender().send(Envelope::new(
+            Origin::Actor(button.id()),
+            Destination::All,
+            UiEvent::Click(1),
+        ));
+        ui.publish(UiEvent::Click(2));
+        ui.publish(UiEvent::Stop);
+        block_on(ui.run());
+
+        control.publish(ControlEvent::Stop);
+        block_on(control.run());
+
+        // Only the click of the button can be answered, the anonymous click
+        // has nobody to reply to.
+        ui.publish(UiEvent::Stop);
+        block_on(ui.run());
+        assert_eq!(log.lock().unwrap().len(), 2);
+        assert_eq!(*done.lock().unwrap(), vec![1]);
+
+        // A message that is asked through the bridge gets the reply.
+        let (ui_sender, control_sender) = (ui.sender().clone(), control.sender().clone());
+        let (_, _, answer) = block_on(async {
+            futures::join!(ui.run(), control.run(), async {
+                let answer = bridge.ask(UiEvent::Click(3)).await;
+                ui_sender.publish(UiEvent::Stop);
+                control_sender.publish(ControlEvent::Stop);
+                answer
+            })
+        });
+        assert!(matches!(answer, Ok(UiEvent::Done(3))));
+        assert_eq!(*done.lock().unwrap(), vec![1]);
+    }
+
+    /// Clicks that were published on the ui commutator, with their origin.
+    struct Clicks {
+        log: Log,
+    }
+
+    impl Actor for Clicks {
+        type Message = UiEvent;
+
+        fn default_subscriptions(&self) -> Vec<UiSignal> {
+            vec![UiSignal::Click]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<UiEvent>) {
+            if let UiEvent::Click(button) = envelope.message {
+                self.log.lock().unwrap().push((button, envelope.origin));
+            }
+        }
+    }
+
+    #[test]
+    fn bridge_in_both_directions() {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let mut control = Commutator::new();
+        control.set_interceptor(|_, message| match message {
+            ControlEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        control.attach(Box::new(Controller {
+            log: commands.clone(),
+        }));
+
+        let clicks = Arc::new(Mutex::new(Vec::new()));
+        let mut ui = Commutator::new();
+        ui.set_interceptor(|_, message| match message {
+            UiEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        ui.attach(Box::new(Clicks {
+            log: clicks.clone(),
+        }));
+
+        let to_control = ui.attach(Box::new(bridge(&control))).id();
+        let to_ui = control
+            .attach(Box::new(Bridge::new(
+                ui.sender(),
+                vec![ControlSignal::Command],
+                |event: &ControlEvent| match event {
+                    ControlEvent::Command(button) => Some(UiEvent::Click(*button)),
+                    _ => None,
+                },
+            )))
+            .id();
+
+        ui.publish(UiEvent::Click(1));
+        control.publish(ControlEvent::Command(2));
+        for _ in 0..3 {
+            ui.publish(UiEvent::Stop);
+            block_on(ui.run());
+            control.publish(ControlEvent::Stop);
+            block_on(control.run());
+        }
+
+        // Every message crosses the bridge once, and isn't bounced back.
+        assert_eq!(
+            *clicks.lock().unwrap(),
+            vec![
+                (1, Origin::Anonymous),
+                (
+                    2,
+                    Origin::Bridged {
+                        bridge: to_ui,
+                        actor: None
+                    }
+                )
+            ]
+        );
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![
+                (2, Origin::Anonymous),
+                (
+                    1,
+                    Origin::Bridged {
+                        bridge: to_control,
+                        actor: None
+                    }
+                )
+            ]
+        );
+        assert!(ui.drain().is_empty());
+        assert!(control.drain().is_empty());
+    }
+
+    /// Buttons that were saved by the storage.
+    struct Storage {
+        saved: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Actor for Storage {
+        type Message = StorageEvent;
+
+        fn default_subscriptions(&self) -> Vec<StorageSignal> {
+            vec![StorageSignal::Save]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<StorageEvent>) {
+            if let StorageEvent::Save(button) = envelope.message {
+                self.saved.lock().unwrap().push(button);
+            }
+        }
+    }
+
+    #[test]
+    fn chain_bridges() {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let mut control = Commutator::new();
+        control.set_interceptor(|_, message| match message {
+            ControlEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        control.attach(Box::new(Controller {
+            log: commands.clone(),
+        }));
+
+        let clicks = Arc::new(Mutex::new(Vec::new()));
+        let mut ui = Commutator::new();
+        ui.set_interceptor(|_, message| match message {
+            UiEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        ui.attach(Box::new(Clicks {
+            log: clicks.clone(),
+        }));
+
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let mut storage = Commutator::new();
+        storage.set_interceptor(|_, message| match message {
+            StorageEvent::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        storage.attach(Box::new(Storage {
+            saved: saved.clone(),
+        }));
+
+        // Bridge ui to control to storage, and back.
+        ui.attach(Box::new(bridge(&control)));
+        control.attach(Box::new(Bridge::new(
+            ui.sender(),
+            vec![ControlSignal::Command],
+            |event: &ControlEvent| match event {
+                ControlEvent::Command(button) => Some(UiEvent::Click(*button)),
+                _ => None,
+            },
+        )));
+        control.attach(Box::new(Bridge::new(
+            storage.sender(),
+            vec![ControlSignal::Command],
+            |event: &ControlEvent| match event {
+                ControlEvent::Command(button) => Some(StorageEvent::Save(*button)),
+                _ => None,
+            },
+        )));
+        storage.attach(Box::new(Bridge::new(
+            control.sender(),
+            vec![StorageSignal::Save],
+            |event: &StorageEvent| match event {
+                StorageEvent::Save(button) => Some(ControlEvent::Command(*button)),
+                _ => None,
+            },
+        )));
+
+        ui.publish(UiEvent::Click(1));
+        storage.publish(StorageEvent::Save(2));
+        for _ in 0..4 {
+            ui.publish(UiEvent::Stop);
+            block_on(ui.run());
+            control.publish(ControlEvent::Stop);
+            block_on(control.run());
+            storage.publish(StorageEvent::Stop);
+            block_on(storage.run());
+        }
+
+        // Both messages cross two bridges, to the commutator at the other end
+        // of the chain, and every commutator sees them once.
+        let buttons = |log: &Log| {
+            log.lock()
+                .unwrap()
+                .iter()
+                .map(|(button, _)| *button)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(buttons(&clicks), vec![1, 2]);
+        assert_eq!(buttons(&commands), vec![1, 2]);
+        assert_eq!(*saved.lock().unwrap(), vec![2, 1]);
+        assert!(ui.drain().is_empty());
+        assert!(control.drain().is_empty());
+        assert!(storage.drain().is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, ActorObject, Commutator, Envelope, MessageType};
+    use armature::{Publisher, Sender};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct Node {
+        name: String,
+        children: usize,
+        sender: Option<Sender<Event>>,
+        log: Log,
+    }
+
+    impl Node {
+        fn new(name: &str, children: usize, log: &Log) -> Self {
+            Self {
+                name: String::from(name),
+                children,
+                sender: None,
+                log: log.clone(),
+            }
+        }
+    }
+
+    impl Actor for Node {
+        type Message = Event;
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn init(&mut self) {
+            self.log.lock().unwrap().push(format!("init {}", self.name));
+            let sender = self.sender.clone().unwrap();
+            let log = self.log.clone();
+            let prefix = self.name.clone();
+            let mut number = 0;
+            let mut factory = move || -> ActorObject<Event> {
+                number += 1;
+                let name = format!("{}.{}", prefix, number);
+                let children = if prefix == "root" && number == 1 {
+                    1
+                } else {
+                    0
+                };
+                Box::new(Node::new(&name, children, &log))
+            };
+            for _ in 0..self.children {
+                sender.spawn_from(&mut factory);
+            }
+        }
+
+        fn deinit(&mut self) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("deinit {}", self.name));
+        }
+
+        fn on_detach(&mut self) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("detach {}", self.name));
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    #[test]
+    fn spawn_and_detach_children() {
+        let log = Log::default();
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+        });
+        let root = commutator.attach(Box::new(Node::new("root", 2, &log)));
+        // Let the commutator process the spawn commands before stopping.
+        let sender = commutator.sender().clone();
+        async_std::task::spawn(async move {
+            async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+            sender.publish(Event::Stop);
+        });
+        block_on(commutator.run());
+
+        let children = commutator.children(root.id()).to_vec();
+        assert_eq!(children.len(), 2);
+        assert_eq!(commutator.parent(children[0]), Some(root.id()));
+        assert_eq!(commutator.children(children[0]).len(), 1);
+        assert_eq!(commutator.handlers().len(), 4);
+
+        commutator.detach(root.id());
+        assert!(commutator.handlers().is_empty());
+        assert!(commutator.children(root.id()).is_empty());
+        assert_eq!(commutator.parent(children[0]), None);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "init root",
+                "init root.1",
+                "init root.2",
+                "init root.1.1",
+                "deinit root.1",
+                "deinit root.1.1",
+                "detach root.1.1",
+                "detach root.1",
+                "deinit root.2",
+                "detach root.2",
+                "detach root",
+            ]
+        );
+    }
+
+    #[test]
+    fn spawned_handles_follow_the_child() {
+        let log = Log::default();
+        let mut commutator = Commutator::new();
+        let root = commutator.attach(Box::new(Node::new("root", 0, &log)));
+        let sender = commutator
+            .get_actor::<Node>(root.id())
+            .unwrap()
+            .sender
+            .clone();
+        let sender = sender.unwrap();
+        assert_eq!(sender.actor(), Some(root.id()));
+
+        let child = sender
+            .spawn(Box::new(Node::new("root.1", 0, &log)))
+            .unwrap();
+        assert!(commutator.drain().is_empty());
+        assert_eq!(commutator.children(root.id()), &[child.id()]);
+        assert!(child.is_alive());
+
+        commutator.detach(root.id());
+        assert!(!child.is_alive());
+
+        // The parent is gone by the time the commutator attaches the child.
+        let orphan = sender
+            .spawn(Box::new(Node::new("orphan", 0, &log)))
+            .unwrap();
+        assert!(commutator.drain().is_empty());
+        assert!(!orphan.is_alive());
+        block_on(orphan.watch());
+        assert!(commutator.handlers().is_empty());
+    }
+
+    #[test]
+    fn spawn_only_through_the_attach_context() {
+        let log = Log::default();
+        let mut commutator = Commutator::new();
+        let root = commutator.attach(Box::new(Node::new("root", 0, &log)));
+        let other = commutator.attach(Box::new(Node::new("other", 0, &log)));
+
+        // The sender of the commutator isn't handed to any actor.
+        let sender = commutator.sender().clone();
+        assert_eq!(sender.actor(), None);
+        assert!(sender
+            .spawn(Box::new(Node::new("stray", 0, &log)))
+            .is_none());
+
+        // A child is always spawned under the actor it was spawned through.
+        let sender = commutator
+            .get_actor::<Node>(other.id())
+            .unwrap()
+            .sender
+            .clone();
+        let child = sender
+            .unwrap()
+            .spawn(Box::new(Node::new("other.1", 0, &log)))
+            .unwrap();
+        assert!(commutator.drain().is_empty());
+        assert_eq!(commutator.parent(child.id()), Some(other.id()));
+        assert!(commutator.children(root.id()).is_empty());
+
+        commutator.detach(root.id());
+        assert!(child.is_alive());
+        commutator.detach(other.id());
+        assert!(!child.is_alive());
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, ActorFactory, CloneableActorObject, Commutator, Envelope, MessageType};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Increment,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[derive(Clone)]
+    struct Counter {
+        count: usize,
+        log: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Actor for Counter {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Increment]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {
+            self.count += 1;
+            self.log.lock().unwrap().push(self.count);
+        }
+    }
+
+    #[test]
+    fn clone_actor_objects() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut template: CloneableActorObject<Event> = Box::new(Counter {
+            count: 10,
+            log: log.clone(),
+        });
+        let snapshot = template.clone();
+
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator.attach(template.create());
+        commutator.attach(template.create());
+        commutator.attach(snapshot);
+        commutator.publish(Event::Increment);
+        commutator.publish(Event::Increment);
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+
+        // Every clone counts on its own, starting from the template's state.
+        assert_eq!(*log.lock().unwrap(), vec![11, 11, 11, 12, 12, 12]);
+    }
+}
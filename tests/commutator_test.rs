@@ -42,7 +42,7 @@ mod tests {
         }
 
         fn init(&mut self) {
-            self.publish(Event::Call(self.id()));
+            self.publish(Event::Call(self.id().unwrap()));
         }
 
         fn default_subscriptions(&self) -> Vec<<Self::Message as armature::Message>::MessageType> {
@@ -53,13 +53,13 @@ mod tests {
             match envelope.message {
                 Event::Call(id) => {
                     println!("Called");
-                    self.post(Event::Respond(self.id()), id);
+                    self.post(Event::Respond(self.id().unwrap()), id);
                 }
                 Event::Respond(id) => {
                     println!("Received {}", id);
                     self.listeners.push(id);
                     if self.listeners.len() >= 3 {
-                        self.publish(Event::Detach(self.id()));
+                        self.publish(Event::Detach(self.id().unwrap()));
                     }
                 }
                 _ => {}
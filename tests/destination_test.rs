@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, GroupId, MessageType, Publisher};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Ping(u8),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Recorder {
+        number: u8,
+        log: Arc<Mutex<Vec<(u8, u8)>>>,
+    }
+
+    impl Actor for Recorder {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping]
+        }
+
+        fn default_groups(&self) -> Vec<GroupId> {
+            if self.number % 2 == 0 {
+                vec!["even".into()]
+            } else {
+                Vec::new()
+            }
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Ping(ping) = envelope.message {
+                self.log.lock().unwrap().push((ping, self.number));
+            }
+        }
+    }
+
+    #[test]
+    fn commutator_destinations() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let ids: Vec<_> = (0..4)
+            .map(|number| {
+                commutator
+                    .attach(Box::new(Recorder {
+                        number,
+                        log: log.clone(),
+                    }))
+                    .id()
+            })
+            .collect();
+        assert!(commutator.join(ids[3], "odd"));
+        assert!(commutator.join(ids[1], "odd"));
+        assert!(!commutator.join(ids[1], "odd"));
+        assert_eq!(commutator.group_members(&"odd".into()), &[ids[3], ids[1]]);
+
+        let sender = commutator.sender().clone();
+        sender.multicast(Event::Ping(0), vec![ids[2], ids[0], ids[2]]);
+        sender.post_to_group(Event::Ping(1), "even");
+        sender.post_to_group(Event::Ping(2), "odd");
+        sender.publish_except(Event::Ping(3), ids[1]);
+        sender.publish(Event::Stop);
+        block_on(commutator.run());
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                (0, 2),
+                (0, 0),
+                (1, 0),
+                (1, 2),
+                (2, 3),
+                (2, 1),
+                (3, 0),
+                (3, 2),
+                (3, 3)
+            ]
+        );
+
+        assert!(commutator.leave(ids[3], "odd"));
+        assert!(!commutator.leave(ids[3], "odd"));
+        commutator.detach(ids[1]);
+        assert!(commutator.group_members(&"odd".into()).is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, MessageType};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Ping,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Recorder {
+        number: usize,
+        log: Arc<Mutex<Vec<(&'static str, usize)>>>,
+    }
+
+    impl Actor for Recorder {
+        type Message = Event;
+
+        fn init(&mut self) {
+            self.log.lock().unwrap().push(("init", self.number));
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {
+            self.log.lock().unwrap().push(("handle", self.number));
+        }
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping]
+        }
+    }
+
+    fn run_once() -> Vec<(&'static str, usize)> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        for number in 0..16 {
+            commutator.attach(Box::new(Recorder {
+                number,
+                log: log.clone(),
+            }));
+        }
+        commutator.publish(Event::Ping);
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        let log = log.lock().unwrap().clone();
+        log
+    }
+
+    #[test]
+    fn dispatch_in_attach_order() {
+        let log = run_once();
+        let expected: Vec<_> = (0..16)
+            .map(|number| ("init", number))
+            .chain((0..16).map(|number| ("handle", number)))
+            .collect();
+        assert_eq!(log, expected);
+        assert_eq!(run_once(), log);
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, ActorId, Commutator, Envelope, HandlerComponent, MessageType, Sender};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Increment,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Counter {
+        count: usize,
+    }
+
+    impl Actor for Counter {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Increment]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {
+            self.count += 1;
+        }
+    }
+
+    struct Idle {
+        _name: &'static str,
+    }
+
+    impl Actor for Idle {
+        type Message = Event;
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    #[derive(Default)]
+    struct Tracked {
+        component: HandlerComponent<Event>,
+    }
+
+    impl Actor for Tracked {
+        type Message = Event;
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+
+        fn component(&self) -> Option<&HandlerComponent<Event>> {
+            Some(&self.component)
+        }
+
+        fn component_mut(&mut self) -> Option<&mut HandlerComponent<Event>> {
+            Some(&mut self.component)
+        }
+    }
+
+    #[test]
+    fn downcast_attached_actors() {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let counter = commutator.attach(Box::new(Counter { count: 0 })).id();
+        let idle = commutator.attach(Box::new(Idle { _name: "idle" })).id();
+        commutator.publish(Event::Increment);
+        commutator.publish(Event::Increment);
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+
+        assert_eq!(commutator.get_actor::<Counter>(counter).unwrap().count, 2);
+        assert!(commutator.get_actor::<Idle>(counter).is_none());
+        assert!(commutator.get_actor::<Counter>(idle).is_none());
+        assert!(commutator.get_actor::<Counter>(0).is_none());
+
+        commutator.get_actor::<Counter>(counter).unwrap().count = 10;
+
+        // A type mismatch leaves the actor attached.
+        assert!(commutator.detach_as::<Idle>(counter).is_none());
+        assert_eq!(commutator.actor_ids(), &[counter, idle]);
+
+        let detached = commutator.detach_as::<Counter>(counter).unwrap();
+        assert_eq!(detached.count, 10);
+        assert_eq!(commutator.actor_ids(), &[idle]);
+        assert!(commutator.detach_as::<Counter>(counter).is_none());
+    }
+
+    #[test]
+    fn id_of_downcast_actor() {
+        let mut commutator = Commutator::new();
+        let tracked = commutator.attach(Box::new(Tracked::default())).id();
+        let idle = commutator.attach(Box::new(Idle { _name: "idle" })).id();
+
+        // The component keeps the id, outside of the commutator calls too.
+        let actor = commutator.get_actor::<Tracked>(tracked).unwrap();
+        assert_eq!(actor.id(), Some(tracked));
+
+        // Without a component, the id is only known while the commutator
+        // calls the actor.
+        assert_eq!(commutator.get_actor::<Idle>(idle).unwrap().id(), None);
+
+        let detached = commutator.detach_as::<Tracked>(tracked).unwrap();
+        assert_eq!(detached.id(), None);
+    }
+
+    struct Leaf;
+
+    impl Actor for Leaf {
+        type Message = Event;
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    struct Inspector {
+        ids: Arc<Mutex<Vec<Option<ActorId>>>>,
+    }
+
+    impl Actor for Inspector {
+        type Message = Event;
+
+        fn on_attach(&mut self, _: &Sender<Event>) {
+            let leaf = Box::new(Leaf);
+            let idle = Idle { _name: "fresh" };
+            let mut ids = self.ids.lock().unwrap();
+            ids.push(self.id());
+            ids.push(leaf.id());
+            ids.push(Leaf.id());
+            ids.push(idle.id());
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    #[test]
+    fn id_of_other_actors() {
+        let mut commutator = Commutator::new();
+        commutator.attach(Box::new(Leaf));
+        let ids = Arc::default();
+        let inspector = commutator
+            .attach(Box::new(Inspector {
+                ids: Arc::clone(&ids),
+            }))
+            .id();
+
+        // Only the actor that is called knows its id, the actors it creates
+        // meanwhile don't have one.
+        assert_eq!(
+            ids.lock().unwrap().as_slice(),
+            &[Some(inspector), None, None, None]
+        );
+    }
+}
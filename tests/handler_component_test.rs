@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::publisher::Publisher;
+    use armature::{
+        Actor, ActorId, Commutator, Envelope, HandlerComponent, MessageType, Origin, Subscription,
+    };
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Ping,
+        Pong,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    /// Switches its subscription from `Ping` to `Pong` on the first ping, and
+    /// answers with a pong in its own name.
+    struct Switcher {
+        component: HandlerComponent<Event>,
+        pongs: Arc<Mutex<Vec<Option<ActorId>>>>,
+    }
+
+    impl Actor for Switcher {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping]
+        }
+
+        fn component(&self) -> Option<&HandlerComponent<Event>> {
+            Some(&self.component)
+        }
+
+        fn component_mut(&mut self) -> Option<&mut HandlerComponent<Event>> {
+            Some(&mut self.component)
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            match envelope.message {
+                Event::Ping => {
+                    assert!(self.component.unsubscribe(Subscription::One(Signal::Ping)));
+                    assert!(self
+                        .component
+                        .subscribe(Subscription::One(Signal::Pong))
+                        .is_some());
+                    // The commutator hasn't executed the requests yet.
+                    assert!(self.component.is_subscribed(Signal::Ping));
+                    self.component.deputy().unwrap().publish(Event::Pong);
+                }
+                Event::Pong => {
+                    let origin = match envelope.origin {
+                        Origin::Actor(id) => Some(id),
+                        _ => None,
+                    };
+                    self.pongs.lock().unwrap().push(origin);
+                    self.component.deputy().unwrap().publish(Event::Stop);
+                }
+                Event::Stop => {}
+            }
+        }
+    }
+
+    fn commutator() -> Commutator<Event> {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+    }
+
+    fn component_subscriptions(commutator: &mut Commutator<Event>, id: ActorId) -> Vec<Signal> {
+        let actor = commutator.get_handler(id).unwrap();
+        let mut subscriptions: Vec<_> = actor
+            .component()
+            .unwrap()
+            .subscriptions()
+            .iter()
+            .copied()
+            .collect();
+        subscriptions.sort_by_key(|sig| sig.index());
+        subscriptions
+    }
+
+    #[test]
+    fn component_follows_subscriptions() {
+        let pongs = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = commutator();
+        let id = commutator
+            .attach(Box::new(Switcher {
+                component: HandlerComponent::new(),
+                pongs: pongs.clone(),
+            }))
+            .id();
+
+        assert_eq!(
+            component_subscriptions(&mut commutator, id),
+            vec![Signal::Ping]
+        );
+        assert_eq!(
+            commutator
+                .get_handler(id)
+                .unwrap()
+                .component()
+                .unwrap()
+                .id(),
+            Some(id)
+        );
+
+        commutator.publish(Event::Ping);
+        block_on(commutator.run());
+
+        assert_eq!(*pongs.lock().unwrap(), vec![Some(id)]);
+        assert_eq!(
+            component_subscriptions(&mut commutator, id),
+            vec![Signal::Pong]
+        );
+        assert_eq!(
+            commutator.subscriptions().subscriptions(id),
+            vec![Signal::Pong]
+        );
+
+        commutator.subscribe(id, Subscription::All);
+        assert_eq!(
+            component_subscriptions(&mut commutator, id),
+            commutator.subscriptions().subscriptions(id)
+        );
+
+        let actor = commutator.detach(id).unwrap();
+        let component = actor.component().unwrap();
+        assert!(!component.is_attached());
+        assert!(component.subscriptions().is_empty());
+    }
+
+    #[test]
+    fn detached_component_does_nothing() {
+        let component = HandlerComponent::<Event>::new();
+        assert!(component
+            .subscribe(Subscription::One(Signal::Ping))
+            .is_none());
+        assert!(!component.unsubscribe(Subscription::One(Signal::Ping)));
+        assert!(component.deputy().is_none());
+        assert!(component.sender().is_none());
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::subscription::Subscription;
+    use armature::{Actor, ActorId, Commutator, Envelope, GroupId, MessageType};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Ping,
+        Pong,
+        Quit,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Pinger;
+
+    impl Actor for Pinger {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping, Signal::Pong]
+        }
+
+        fn default_groups(&self) -> Vec<GroupId> {
+            vec![GroupId::from("players")]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    struct Watcher<T> {
+        _marker: Option<T>,
+    }
+
+    impl<T> Actor for Watcher<T>
+    where
+        T: Send + 'static,
+    {
+        type Message = Event;
+
+        fn initial_subscriptions(&self) -> Vec<Subscription<Event>> {
+            vec![
+                Subscription::One(Signal::Pong),
+                Subscription::Predicate(|_| true),
+            ]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    /// Create a commutator with a named pinger and a watcher that it spawned.
+    fn commutator() -> (Commutator<Event>, ActorId, ActorId) {
+        let mut commutator = Commutator::new();
+        let pinger = commutator
+            .attach_named("pinger", Box::new(Pinger))
+            .unwrap()
+            .id();
+        let watcher = Box::new(Watcher::<u8> { _marker: None });
+        let watcher = commutator.attach_child(pinger, watcher).unwrap().id();
+        for event in [Event::Ping, Event::Pong, Event::Quit] {
+            commutator.publish(event);
+        }
+        (commutator, pinger, watcher)
+    }
+
+    #[test]
+    fn introspect_actors() {
+        let (commutator, pinger, watcher) = commutator();
+        let topology = commutator.introspect();
+        assert_eq!(topology.message_types, vec!["Ping", "Pong", "Quit"]);
+        assert_eq!(topology.queue_length, 3);
+        assert_eq!(topology.actors.len(), 2);
+
+        let info = topology.actor(pinger).unwrap();
+        assert_eq!(info.names, vec![String::from("pinger")]);
+        assert!(info.type_name.ends_with("Pinger"));
+        assert_eq!(info.subscriptions, vec!["Ping", "Pong"]);
+        assert_eq!(info.predicates, 0);
+        assert_eq!(info.groups, vec![GroupId::from("players")]);
+        assert_eq!(info.parent, None);
+        assert_eq!(info.label(), "pinger");
+
+        let info = topology.actor(watcher).unwrap();
+        assert!(info.names.is_empty());
+        assert_eq!(info.subscriptions, vec!["Pong"]);
+        assert_eq!(info.predicates, 1);
+        assert_eq!(info.parent, Some(pinger));
+        assert_eq!(info.label(), "Watcher<u8>");
+    }
+
+    #[test]
+    fn render_dot_and_mermaid() {
+        let (commutator, pinger, watcher) = commutator();
+        let topology = commutator.introspect();
+
+        let dot = topology.to_dot();
+        assert!(dot.starts_with("digraph armature {\n"));
+        assert!(dot.ends_with("}\n"));
+        for line in [
+            String::from("    \"type_Ping\" [label=\"Ping\", shape=ellipse];"),
+            format!("    \"actor_{}\" [label=\"pinger\", shape=box];", pinger),
+            format!(
+                "    \"actor_{}\" [label=\"Watcher<u8>\", shape=box];",
+                watcher
+            ),
+            format!("    \"type_Pong\" -> \"actor_{}\";", watcher),
+            String::from("    \"predicate\" [label=\"predicate\", shape=diamond];"),
+            format!("    \"predicate\" -> \"actor_{}\";", watcher),
+            format!(
+                "    \"actor_{}\" -> \"actor_{}\" [style=dashed];",
+                pinger, watcher
+            ),
+        ] {
+            assert!(dot.lines().any(|l| l == line), "missing {}", line);
+        }
+        // Message types without subscribers are left out.
+        assert!(!dot.contains("type_Quit"));
+        assert!(!dot.contains(&format!("\"predicate\" -> \"actor_{}\"", pinger)));
+
+        let mermaid = topology.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        for line in [
+            String::from("    type_Ping([\"Ping\"])"),
+            format!("    actor_{}[\"pinger\"]", pinger),
+            format!("    type_Ping --> actor_{}", pinger),
+            String::from("    predicate{\"predicate\"}"),
+            format!("    predicate --> actor_{}", watcher),
+            format!("    actor_{} -.-> actor_{}", pinger, watcher),
+        ] {
+            assert!(mermaid.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
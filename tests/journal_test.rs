@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::journal::Entry;
+    use armature::{
+        Actor, ActorId, Commutator, Destination, Envelope, Journal, MessageType, Origin, Sender,
+    };
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, MessageType)]
+    #[cfg_attr(feature = "journal", derive(serde::Serialize, serde::Deserialize))]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Tick(u32),
+        Echo(u32),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// Logs the time of every tick, and echoes it to the listener.
+    struct Stamper {
+        listener: ActorId,
+        sender: Option<Sender<Event>>,
+        log: Log,
+    }
+
+    impl Actor for Stamper {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Tick]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Tick(n) = envelope.message {
+                let sender = self.sender.as_ref().unwrap();
+                let line = format!("tick {} at {:?}", n, sender.now());
+                self.log.lock().unwrap().push(line);
+                let echo = Envelope::new(
+                    Origin::Actor(self.id().unwrap()),
+                    Destination::Single(self.listener),
+                    Event::Echo(n),
+                );
+                sender.unbounded_send(echo).unwrap();
+            }
+        }
+    }
+
+    /// Logs the echoes and who sent them.
+    struct Listener {
+        log: Log,
+    }
+
+    impl Actor for Listener {
+        type Message = Event;
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let (Event::Echo(n), Origin::Actor(from)) = (&envelope.message, envelope.origin) {
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("echo {} from {}", n, from));
+            }
+        }
+    }
+
+    /// Create a commutator with a listener and a stamper, and return the id
+    /// of the stamper.
+    fn commutator(log: &Log) -> (Commutator<Event>, ActorId) {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let listener = commutator
+            .attach(Box::new(Listener { log: log.clone() }))
+            .id();
+        let stamper = commutator
+            .attach(Box::new(Stamper {
+                listener,
+                sender: None,
+                log: log.clone(),
+            }))
+            .id();
+        (commutator, stamper)
+    }
+
+    /// Get the log, with the id of the stamper replaced by its name.
+    fn lines(log: &Log, stamper: ActorId) -> Vec<String> {
+        log.lock()
+            .unwrap()
+            .iter()
+            .map(|line| line.replace(&stamper.to_string(), "stamper"))
+            .collect()
+    }
+
+    /// Run the commutator while ticks are published with some delay.
+    fn run_with_ticks(commutator: &mut Commutator<Event>) {
+        let sender = commutator.sender().clone();
+        let ticker = thread::spawn(move || {
+            for n in 1..=3 {
+                thread::sleep(Duration::from_millis(10));
+                let tick = Envelope::new(Origin::Anonymous, Destination::All, Event::Tick(n));
+                sender.unbounded_send(tick).unwrap();
+            }
+            thread::sleep(Duration::from_millis(10));
+            let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+            sender.unbounded_send(stop).unwrap();
+        });
+        block_on(commutator.run());
+        ticker.join().unwrap();
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let recorded_log = Log::default();
+        let (mut recording, recorded_stamper) = commutator(&recorded_log);
+        recording.record(Journal::new());
+        run_with_ticks(&mut recording);
+        let journal = recording.stop_recording().unwrap().into_journal().unwrap();
+        // Two attached actors, three ticks, three echoes and the stop.
+        assert_eq!(journal.len(), 9);
+
+        let replayed_log = Log::default();
+        let (mut replaying, replayed_stamper) = commutator(&replayed_log);
+        replaying.replay(journal);
+
+        let recorded = lines(&recorded_log, recorded_stamper);
+        assert_eq!(recorded.len(), 6);
+        assert_eq!(lines(&replayed_log, replayed_stamper), recorded);
+        assert!(!replaying.clock().is_virtual());
+    }
+
+    #[test]
+    fn ask_while_recording() {
+        let log = Log::default();
+        let (mut commutator, stamper) = commutator(&log);
+        commutator.record(Journal::new());
+        let stamper = commutator.actor_ref(stamper).unwrap();
+        let sender = commutator.sender().clone();
+        let (_, answer) = block_on(async {
+            futures::join!(commutator.run(), async {
+                // The stamper doesn't reply, so the ask fails once the
+                // envelope is dropped, even though the journal keeps a copy.
+                let timeout = Duration::from_secs(5);
+                let answer = async_std::future::timeout(timeout, stamper.ask(Event::Tick(1))).await;
+                let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+                sender.unbounded_send(stop).unwrap();
+                answer
+            })
+        });
+        assert!(matches!(answer, Ok(Err(_))));
+        let journal = commutator.stop_recording().unwrap().into_journal().unwrap();
+        assert!(journal.entries().iter().all(|entry| match entry {
+            Entry::Received { envelope, .. } => !envelope.expects_reply(),
+            Entry::Attached { .. } => true,
+        }));
+    }
+
+    #[cfg(feature = "journal")]
+    #[test]
+    fn replay_from_file() {
+        use armature::JournalFile;
+
+        let path = std::env::temp_dir().join(format!("armature-{}.journal", std::process::id()));
+        let recorded_log = Log::default();
+        let (mut recording, recorded_stamper) = commutator(&recorded_log);
+        recording.record(JournalFile::create(&path).unwrap());
+        run_with_ticks(&mut recording);
+        drop(recording.stop_recording());
+
+        let journal = Journal::<Event>::load(&path).unwrap();
+        assert_eq!(journal.len(), 9);
+        let replayed_log = Log::default();
+        let (mut replaying, replayed_stamper) = commutator(&replayed_log);
+        replaying.replay(journal);
+
+        assert_eq!(
+            lines(&replayed_log, replayed_stamper),
+            lines(&recorded_log, recorded_stamper)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::MessageType;
+    use armature::MessageTypeInfo;
+
+    #[allow(dead_code)]
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Press { key: char },
+        Release(char),
+        Tick,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[test]
+    fn message_type_enumeration() {
+        assert_eq!(Signal::COUNT, 3);
+        assert_eq!(Signal::ALL, &[Signal::Press, Signal::Release, Signal::Tick]);
+        for (index, sig) in Signal::ALL.iter().enumerate() {
+            assert_eq!(sig.index(), index);
+        }
+        assert_eq!(Signal::Release.name(), "Release");
+        assert_eq!(Signal::from(&Event::Press { key: 'a' }), Signal::Press);
+    }
+
+    #[test]
+    fn message_type_info_is_generic() {
+        fn names<T: MessageTypeInfo>() -> Vec<&'static str> {
+            T::ALL.iter().map(|sig| sig.name()).collect()
+        }
+        assert_eq!(names::<Signal>(), vec!["Press", "Release", "Tick"]);
+        assert_eq!(<Signal as MessageTypeInfo>::COUNT, Signal::COUNT);
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "InputSignal")]
+    pub enum Input {
+        #[message_type(priority = "high", group = "input", group = "keyboard")]
+        Key(char),
+        #[message_type(group = "input")]
+        Click,
+        #[message_type(skip, priority = "low")]
+        Internal,
+    }
+
+    impl armature::Message for Input {
+        type MessageType = InputSignal;
+    }
+
+    impl armature::MessageType for InputSignal {
+        type Message = Input;
+    }
+
+    #[test]
+    fn message_type_variant_attributes() {
+        use armature::Priority;
+
+        assert_eq!(InputSignal::Key.priority(), Priority::High);
+        assert_eq!(InputSignal::Click.priority(), Priority::Normal);
+        assert_eq!(InputSignal::Internal.priority(), Priority::Low);
+
+        assert_eq!(InputSignal::Key.groups(), &["input", "keyboard"]);
+        assert!(InputSignal::Click.in_group("input"));
+        assert!(!InputSignal::Click.in_group("keyboard"));
+        assert!(InputSignal::Internal.groups().is_empty());
+
+        assert!(!InputSignal::Key.is_skipped());
+        assert!(InputSignal::Internal.is_skipped());
+        assert!(<InputSignal as MessageTypeInfo>::is_skipped(
+            &InputSignal::Internal
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Destination, Envelope, MessageType, Metrics, Origin};
+    use async_std::task::block_on;
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Work,
+        Ignored,
+        Skip,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Worker {
+        done: usize,
+    }
+
+    impl Actor for Worker {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Work]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {
+            self.done += 1;
+        }
+    }
+
+    /// Run a commutator with a named worker, and get its metrics before and
+    /// after it ran.
+    fn run() -> (Metrics, Metrics) {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Skip => InterceptResult::Interception,
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+            .attach_named("worker", Box::new(Worker { done: 0 }))
+            .unwrap();
+        for event in [Event::Work, Event::Work, Event::Ignored, Event::Skip] {
+            commutator.publish(event);
+        }
+        let gone = Envelope::new(Origin::Anonymous, Destination::Single(0), Event::Work);
+        commutator.sender().unbounded_send(gone).unwrap();
+        commutator.publish(Event::Stop);
+
+        let before = commutator.metrics();
+        block_on(commutator.run());
+        (before, commutator.metrics())
+    }
+
+    #[test]
+    fn counts_dispatched_and_dropped_envelopes() {
+        let (before, after) = run();
+        assert_eq!(before.queue_length, 6);
+        assert_eq!(before.received, 0);
+
+        assert_eq!(after.queue_length, 0);
+        assert_eq!(after.received, 6);
+        assert_eq!(
+            after.dispatched,
+            vec![("Work", 3), ("Ignored", 1), ("Skip", 0), ("Stop", 0)]
+        );
+        // The skipped envelope and the stop.
+        assert_eq!(after.dropped, 2);
+        // The ignored envelope and the envelope to the missing actor.
+        assert_eq!(after.dead_letters, 2);
+
+        assert_eq!(after.actors.len(), 1);
+        let worker = &after.actors[0];
+        assert_eq!(worker.name.as_deref(), Some("worker"));
+        assert_eq!(worker.handled, 2);
+        assert_eq!(worker.handling_time.count(), 2);
+        let buckets = worker.handling_time.cumulative();
+        assert_eq!(buckets.last(), Some(&(None, 2)));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn render_prometheus_text() {
+        let (_, metrics) = run();
+        let text = metrics.to_prometheus("armature");
+        let id = metrics.actors[0].id;
+        let labels = format!("actor=\"{}\",name=\"worker\"", id);
+        for line in [
+            String::from("# TYPE armature_queue_length gauge"),
+            String::from("armature_queue_length 0"),
+            String::from("armature_received_total 6"),
+            String::from("armature_dispatched_total{message_type=\"Work\"} 3"),
+            String::from("armature_dropped_total 2"),
+            String::from("armature_dead_letters_total 2"),
+            format!("armature_handled_total{{{}}} 2", labels),
+            String::from("# TYPE armature_handling_seconds histogram"),
+            format!(
+                "armature_handling_seconds_bucket{{{},le=\"+Inf\"}} 2",
+                labels
+            ),
+            format!("armature_handling_seconds_count{{{}}} 2", labels),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
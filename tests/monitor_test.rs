@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, ActorId, Commutator, Envelope, MessageType, Sender};
+    use armature::{SystemMessage, TerminationReason};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Crash,
+        Detach(ActorId),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    type Log = Arc<Mutex<Vec<(ActorId, TerminationReason)>>>;
+
+    struct Watcher {
+        sender: Option<Sender<Event>>,
+        targets: Vec<ActorId>,
+        log: Log,
+    }
+
+    impl Actor for Watcher {
+        type Message = Event;
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn init(&mut self) {
+            // Monitors can also be set up from within an actor.
+            let sender = self.sender.as_ref().unwrap();
+            for target in self.targets.iter() {
+                sender.monitor(*target);
+            }
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+
+        fn handle_system(&mut self, envelope: &Envelope<SystemMessage>) {
+            if let SystemMessage::Terminated { id, reason } = &envelope.message {
+                self.log.lock().unwrap().push((*id, reason.clone()));
+            }
+        }
+    }
+
+    struct Worker;
+
+    impl Actor for Worker {
+        type Message = Event;
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Crash = envelope.message {
+                panic!("boom");
+            }
+        }
+    }
+
+    #[test]
+    fn monitor_terminations() {
+        let log = Log::default();
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|commutator, message| match message {
+            Event::Detach(id) => {
+                commutator.detach(id);
+                InterceptResult::Interception
+            }
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let detached = commutator.attach(Box::new(Worker)).id();
+        let shutdown = commutator.attach(Box::new(Worker)).id();
+        let crashed = commutator.attach(Box::new(Worker));
+        let watcher = commutator
+            .attach_and_init(Box::new(Watcher {
+                sender: None,
+                targets: vec![detached, shutdown],
+                log: log.clone(),
+            }))
+            .id();
+        assert!(commutator.monitor(watcher, crashed.id()));
+        assert!(commutator.monitor(watcher, 0));
+        assert!(!commutator.monitor(0, crashed.id()));
+
+        commutator.publish(Event::Detach(detached));
+        crashed.post(Event::Crash).unwrap();
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        assert!(!crashed.is_alive());
+        assert!(commutator.handlers().contains_key(&shutdown));
+
+        // The watchers are notified when `run` stops, and not again when the
+        // commutator is dropped.
+        let expected = vec![
+            (0, TerminationReason::NotAttached),
+            (detached, TerminationReason::Detached),
+            (
+                crashed.id(),
+                TerminationReason::Panicked(String::from("boom")),
+            ),
+            (shutdown, TerminationReason::Shutdown),
+        ];
+        assert_eq!(*log.lock().unwrap(), expected);
+        drop(commutator);
+        assert_eq!(*log.lock().unwrap(), expected);
+    }
+}
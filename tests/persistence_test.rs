@@ -0,0 +1,273 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{
+        Actor, Commutator, Destination, Envelope, MemoryStorage, MessageType, Origin, Persisted,
+        Persistent, Publisher, Sender, Storage,
+    };
+    use async_std::task::block_on;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, MessageType)]
+    #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Add(u32),
+        Total(u32),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    /// Sums the numbers it is sent, and takes a snapshot every three.
+    #[derive(Debug, Default)]
+    struct Counter {
+        sum: u32,
+        restored: Option<u32>,
+    }
+
+    impl Actor for Counter {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Add]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Add(n) = envelope.message {
+                self.sum += n;
+            }
+        }
+    }
+
+    impl Persistent for Counter {
+        type Snapshot = u32;
+
+        fn persistence_key(&self) -> String {
+            "counter".to_string()
+        }
+
+        fn snapshot(&self) -> u32 {
+            self.sum
+        }
+
+        fn restore(&mut self, snapshot: u32) {
+            self.sum = snapshot;
+            self.restored = Some(snapshot);
+        }
+
+        fn snapshot_interval(&self) -> usize {
+            3
+        }
+    }
+
+    /// Attach a counter to a new commutator, publish the numbers and run the
+    /// commutator until they are handled. Return the counter.
+    fn run<S>(storage: S, numbers: &[u32]) -> Counter
+    where
+        S: Storage<Event, u32> + 'static,
+    {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let id = commutator
+            .attach_persistent(Counter::default(), storage)
+            .id();
+        let sender = commutator.sender().clone();
+        for n in numbers {
+            let add = Envelope::new(Origin::Anonymous, Destination::All, Event::Add(*n));
+            sender.unbounded_send(add).unwrap();
+        }
+        let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+        sender.unbounded_send(stop).unwrap();
+        block_on(commutator.run());
+        commutator
+            .detach_as::<Persisted<Counter, S>>(id)
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn rebuild_from_memory() {
+        let storage = MemoryStorage::new();
+        let counter = run(storage.clone(), &[1, 2, 3, 4, 5]);
+        assert_eq!(counter.sum, 15);
+        assert_eq!(counter.restored, None);
+
+        // The snapshot was taken after 1, 2 and 3; 4 and 5 are handled again.
+        let counter = run(storage.clone(), &[10]);
+        assert_eq!(counter.restored, Some(6));
+        assert_eq!(counter.sum, 25);
+
+        let counter = run(storage, &[]);
+        assert_eq!(counter.sum, 25);
+    }
+
+    #[test]
+    fn ask_persistent_actor() {
+        let storage = MemoryStorage::new();
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let counter = commutator.attach_persistent(Counter::default(), storage.clone());
+        let sender = commutator.sender().clone();
+        let (_, answer) = block_on(async {
+            futures::join!(commutator.run(), async {
+                // The counter doesn't reply, so the ask fails once the
+                // envelope is dropped, even though the event log keeps a copy.
+                let timeout = Duration::from_secs(5);
+                let answer = async_std::future::timeout(timeout, counter.ask(Event::Add(1))).await;
+                let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+                sender.unbounded_send(stop).unwrap();
+                answer
+            })
+        });
+        assert!(matches!(answer, Ok(Err(_))));
+        assert_eq!(run(storage, &[]).sum, 1);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn rebuild_from_files() {
+        use armature::FileStorage;
+
+        let directory =
+            std::env::temp_dir().join(format!("armature-{}-persistence", std::process::id()));
+        let counter = run(FileStorage::open(&directory).unwrap(), &[1, 2, 3, 4]);
+        assert_eq!(counter.sum, 10);
+
+        let counter = run(FileStorage::open(&directory).unwrap(), &[]);
+        assert_eq!(counter.restored, Some(6));
+        assert_eq!(counter.sum, 10);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn rebuild_after_crash_while_saving_a_snapshot() {
+        use armature::FileStorage;
+
+        let directory = std::env::temp_dir().join(format!("armature-{}-crash", std::process::id()));
+        let events = directory.join("counter.events");
+        run(FileStorage::open(&directory).unwrap(), &[1, 2]);
+        let log = std::fs::read(&events).unwrap();
+        // The snapshot is saved after 3, and the log is truncated.
+        let counter = run(FileStorage::open(&directory).unwrap(), &[3]);
+        assert_eq!(counter.sum, 6);
+
+        // Restore the log as if the process crashed before it was truncated.
+        std::fs::write(&events, log).unwrap();
+        let counter = run(FileStorage::open(&directory).unwrap(), &[4]);
+        assert_eq!(counter.restored, Some(6));
+        assert_eq!(counter.sum, 10);
+        let counter = run(FileStorage::open(&directory).unwrap(), &[]);
+        assert_eq!(counter.sum, 10);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /// Publishes the running total of the numbers it is sent.
+    #[derive(Default)]
+    struct Totalizer {
+        sum: u32,
+        sender: Option<Sender<Event>>,
+    }
+
+    impl Actor for Totalizer {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Add]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Add(n) = envelope.message {
+                self.sum += n;
+                self.publish(Event::Total(self.sum));
+            }
+        }
+    }
+
+    impl Publisher for Totalizer {
+        type Message = Event;
+
+        fn sender(&self) -> &Sender<Event> {
+            self.sender.as_ref().unwrap()
+        }
+    }
+
+    impl Persistent for Totalizer {
+        type Snapshot = u32;
+
+        fn persistence_key(&self) -> String {
+            "totalizer".to_string()
+        }
+
+        fn snapshot(&self) -> u32 {
+            self.sum
+        }
+
+        fn restore(&mut self, snapshot: u32) {
+            self.sum = snapshot;
+        }
+    }
+
+    fn totals(envelopes: Vec<Envelope<Event>>) -> Vec<u32> {
+        envelopes
+            .into_iter()
+            .filter_map(|envelope| match envelope.message {
+                Event::Total(total) => Some(total),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recover_actor_that_publishes() {
+        let storage = MemoryStorage::new();
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator.attach_persistent(Totalizer::default(), storage.clone());
+        commutator.publish(Event::Add(1));
+        commutator.publish(Event::Add(2));
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        assert_eq!(totals(commutator.drain()), vec![1, 3]);
+
+        // The totals aren't published again while the actor is rebuilt.
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let id = commutator
+            .attach_persistent(Totalizer::default(), storage)
+            .id();
+        assert!(commutator.drain().is_empty());
+        commutator.publish(Event::Add(3));
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        assert_eq!(totals(commutator.drain()), vec![6]);
+
+        let topology = commutator.introspect();
+        assert!(topology.actors[0].type_name.ends_with("::Totalizer"));
+        assert_eq!(topology.actors[0].id, id);
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, MessageType};
+    use armature::{Publisher, RegistryError, Sender};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Toggle,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    struct Led {
+        on: Arc<Mutex<bool>>,
+    }
+
+    impl Actor for Led {
+        type Message = Event;
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Toggle = envelope.message {
+                let mut on = self.on.lock().unwrap();
+                *on = !*on;
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Switch {
+        sender: Option<Sender<Event>>,
+    }
+
+    impl Actor for Switch {
+        type Message = Event;
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn init(&mut self) {
+            let led = self.resolve("led-3").expect("led-3 is registered");
+            self.post(Event::Toggle, led);
+            self.publish(Event::Stop);
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    impl Publisher for Switch {
+        type Message = Event;
+
+        fn sender(&self) -> &Sender<Event> {
+            self.sender.as_ref().unwrap()
+        }
+    }
+
+    #[test]
+    fn registry_lookup() {
+        let on = Arc::new(Mutex::new(false));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let led = commutator
+            .attach_named("led-3", Box::new(Led { on: on.clone() }))
+            .unwrap();
+        assert_eq!(
+            commutator.attach_named("led-3", Box::new(Led { on: on.clone() })),
+            Err(RegistryError::NameTaken {
+                name: String::from("led-3"),
+                id: led.id()
+            })
+        );
+        assert_eq!(commutator.handlers().len(), 1);
+
+        let switch = commutator.attach(Box::new(Switch::default()));
+        assert_eq!(commutator.register("switch", switch.id()), Ok(()));
+        assert_eq!(
+            commutator.register("led-3", switch.id()),
+            Err(RegistryError::NameTaken {
+                name: String::from("led-3"),
+                id: led.id()
+            })
+        );
+        assert_eq!(
+            commutator.register("ghost", 0),
+            Err(RegistryError::NotAttached(0))
+        );
+        block_on(commutator.run());
+        assert!(*on.lock().unwrap());
+
+        commutator.detach(led.id());
+        assert_eq!(commutator.resolve("led-3"), None);
+        assert_eq!(commutator.sender().resolve("switch"), Some(switch.id()));
+        assert_eq!(commutator.unregister("switch"), Some(switch.id()));
+        assert!(commutator.registry().names_of(switch.id()).is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+
+    use armature::system::SystemMessageType;
+    use armature::{
+        Destination, Envelope, GroupId, MessageType, Origin, SystemMessage, TerminationReason,
+    };
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, MessageType, Serialize, Deserialize)]
+    #[message_type(name = "Signal", serde)]
+    pub enum Event {
+        Move { x: i32, y: i32 },
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[test]
+    fn envelope_roundtrip() {
+        let envelope = Envelope::new(
+            Origin::Actor(7),
+            Destination::Group(GroupId::from("ui")),
+            Event::Move { x: 1, y: -2 },
+        );
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert_eq!(
+            json,
+            r#"{"origin":{"Actor":7},"destination":{"Group":"ui"},"message":{"Move":{"x":1,"y":-2}}}"#
+        );
+
+        let decoded: Envelope<Event> = serde_json::from_str(&json).unwrap();
+        assert!(matches!(decoded.origin, Origin::Actor(7)));
+        assert!(
+            matches!(decoded.destination, Destination::Group(ref group) if group.as_str() == "ui")
+        );
+        assert_eq!(decoded.message, Event::Move { x: 1, y: -2 });
+        assert!(!decoded.expects_reply());
+    }
+
+    #[test]
+    fn message_types_roundtrip() {
+        let json = serde_json::to_string(&[Signal::Move, Signal::Stop]).unwrap();
+        assert_eq!(json, r#"["Move","Stop"]"#);
+        let decoded: Vec<Signal> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, vec![Signal::Move, Signal::Stop]);
+
+        let message = SystemMessage::Terminated {
+            id: 3,
+            reason: TerminationReason::Panicked(String::from("boom")),
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        let decoded: SystemMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, message);
+        let json = serde_json::to_string(&SystemMessageType::Terminated).unwrap();
+        assert_eq!(json, r#""Terminated""#);
+    }
+}
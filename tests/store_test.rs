@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{
+        Actor, Commutator, Destination, Envelope, Key, MessageType, Origin, Sender, SystemMessage,
+    };
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    const VOLUME: Key<u8> = Key::new("volume");
+    const TITLE: Key<String> = Key::new("title");
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        SetVolume(u8),
+        Mute,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    type Log = Arc<Mutex<Vec<Option<u8>>>>;
+
+    /// Writes the volume to the store, and stops the commutator once it is
+    /// muted.
+    struct Mixer {
+        sender: Option<Sender<Event>>,
+    }
+
+    impl Actor for Mixer {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::SetVolume, Signal::Mute]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            let sender = self.sender.as_ref().unwrap();
+            match envelope.message {
+                Event::SetVolume(volume) => sender.store_value(&VOLUME, volume),
+                Event::Mute => {
+                    sender.remove_value(&VOLUME);
+                    let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+                    sender.unbounded_send(stop).unwrap();
+                }
+                Event::Stop => {}
+            }
+        }
+    }
+
+    /// Watches the volume, and logs it when it changes.
+    struct Display {
+        sender: Option<Sender<Event>>,
+        log: Log,
+    }
+
+    impl Actor for Display {
+        type Message = Event;
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn init(&mut self) {
+            self.sender.as_ref().unwrap().watch_key(&VOLUME);
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+
+        fn handle_system(&mut self, envelope: &Envelope<SystemMessage>) {
+            if let SystemMessage::Changed { key } = &envelope.message {
+                assert_eq!(key, VOLUME.name());
+                let volume = self.sender.as_ref().unwrap().store().get(&VOLUME);
+                self.log.lock().unwrap().push(volume);
+            }
+        }
+    }
+
+    fn commutator() -> Commutator<Event> {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+    }
+
+    #[test]
+    fn watchers_are_notified_of_changes() {
+        let log = Log::default();
+        let mut commutator = commutator();
+        commutator.attach(Box::new(Mixer { sender: None }));
+        commutator.attach(Box::new(Display {
+            sender: None,
+            log: log.clone(),
+        }));
+        commutator.store_value(&TITLE, String::from("radio"));
+
+        for event in [Event::SetVolume(3), Event::SetVolume(7), Event::Mute] {
+            commutator.publish(event);
+        }
+        block_on(commutator.run());
+
+        assert_eq!(*log.lock().unwrap(), vec![Some(3), Some(7), None]);
+        assert_eq!(commutator.store().get(&TITLE), Some(String::from("radio")));
+        assert_eq!(commutator.store().keys(), vec![String::from("title")]);
+    }
+
+    #[test]
+    fn watches_end_with_the_actor() {
+        let log = Log::default();
+        let mut commutator = commutator();
+        let display = commutator
+            .attach(Box::new(Display {
+                sender: None,
+                log: log.clone(),
+            }))
+            .id();
+        assert!(commutator.watch_key(display, &VOLUME));
+
+        commutator.store_value(&VOLUME, 5);
+        // Reading a value with another type fails.
+        let wrong: Key<u32> = Key::new("volume");
+        assert_eq!(commutator.store().get(&wrong), None);
+        assert_eq!(
+            commutator.store().with(&VOLUME, |volume| *volume * 2),
+            Some(10)
+        );
+
+        commutator.detach(display);
+        commutator.store_value(&VOLUME, 6);
+        assert!(!commutator.unwatch_key(display, &VOLUME));
+        assert_eq!(*log.lock().unwrap(), vec![Some(5)]);
+    }
+
+    #[test]
+    fn keys_with_another_type_have_their_own_value() {
+        let mut commutator = commutator();
+        let wide: Key<u32> = Key::new("volume");
+        commutator.store_value(&VOLUME, 5);
+        commutator.store_value(&wide, 500);
+        assert_eq!(commutator.store().get(&VOLUME), Some(5));
+        assert_eq!(commutator.store().get(&wide), Some(500));
+        assert_eq!(commutator.store().keys(), vec![String::from("volume")]);
+
+        commutator.remove_value(&wide);
+        assert!(commutator.store().contains(&VOLUME));
+        assert!(!commutator.store().contains(&wide));
+    }
+}
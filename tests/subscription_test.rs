@@ -0,0 +1,350 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, MessageType, Sender};
+    use armature::{Subscription, SubscriptionFilter, SubscriptionTable};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+
+    #[allow(dead_code)]
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        #[message_type(group = "input")]
+        Press(u8),
+        #[message_type(group = "input")]
+        Release(u8),
+        Tick,
+        #[message_type(skip)]
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[test]
+    fn subscription_table() {
+        let mut table = SubscriptionTable::<Signal>::new();
+        assert!(table.insert(Signal::Press, 1));
+        assert!(table.insert(Signal::Press, 2));
+        assert!(!table.insert(Signal::Press, 1));
+        assert!(table.insert(Signal::Tick, 1));
+        assert_eq!(table.subscribers(Signal::Press), &[1, 2]);
+        assert!(table.subscribers(Signal::Release).is_empty());
+        assert_eq!(table.subscriptions(1), vec![Signal::Press, Signal::Tick]);
+
+        assert!(table.remove(Signal::Press, 2));
+        assert!(!table.remove(Signal::Press, 2));
+        table.remove_actor(1);
+        assert!(table.subscribers(Signal::Press).is_empty());
+        assert!(table.subscribers(Signal::Tick).is_empty());
+    }
+
+    #[test]
+    fn subscription_kinds() {
+        let mut table = SubscriptionTable::<Signal>::new();
+        assert_eq!(
+            table.subscribe(Subscription::All, 1).1,
+            vec![Signal::Press, Signal::Release, Signal::Tick]
+        );
+        assert_eq!(
+            table.subscribe(Subscription::Group("input"), 2).1,
+            vec![Signal::Press, Signal::Release]
+        );
+        assert!(table
+            .subscribe(
+                Subscription::Predicate(|event| matches!(event, Event::Press(0))),
+                3
+            )
+            .1
+            .is_empty());
+        assert!(table
+            .subscribe(Subscription::One(Signal::Stop), 4)
+            .1
+            .is_empty());
+
+        assert_eq!(table.route(&Event::Press(0)).as_slice(), &[1, 2, 3]);
+        assert_eq!(table.route(&Event::Press(1)).as_slice(), &[1, 2]);
+        assert_eq!(table.route(&Event::Tick).as_slice(), &[1]);
+        assert!(table.route(&Event::Stop).is_empty());
+
+        assert_eq!(
+            table.unsubscribe(Subscription::Group("input"), 1),
+            vec![Signal::Press, Signal::Release]
+        );
+        assert_eq!(table.route(&Event::Press(0)).as_slice(), &[2, 3]);
+    }
+
+    #[test]
+    fn overlapping_subscriptions() {
+        let mut table = SubscriptionTable::<Signal>::new();
+        assert_eq!(
+            table.subscribe(Subscription::One(Signal::Press), 1).1,
+            vec![Signal::Press]
+        );
+        assert_eq!(
+            table.subscribe(Subscription::All, 1).1,
+            vec![Signal::Release, Signal::Tick]
+        );
+        assert!(table
+            .subscribe(Subscription::Group("input"), 1)
+            .1
+            .is_empty());
+
+        // Press is still covered by the single subscription, and Release by
+        // the group.
+        assert_eq!(table.unsubscribe(Subscription::All, 1), vec![Signal::Tick]);
+        assert_eq!(table.subscriptions(1), vec![Signal::Press, Signal::Release]);
+        assert_eq!(
+            table.unsubscribe(Subscription::Group("input"), 1),
+            vec![Signal::Release]
+        );
+        assert_eq!(
+            table.unsubscribe(Subscription::One(Signal::Press), 1),
+            vec![Signal::Press]
+        );
+        assert!(table.subscriptions(1).is_empty());
+    }
+
+    fn is_release(event: &Event) -> bool {
+        matches!(event, Event::Release(_))
+    }
+
+    #[test]
+    fn unsubscribe_predicates_by_id() {
+        let mut table = SubscriptionTable::<Signal>::new();
+        let (first, _) = table.subscribe(Subscription::Predicate(is_release), 1);
+        let (second, _) = table.subscribe(Subscription::Predicate(is_release), 2);
+        let (third, _) = table.subscribe(Subscription::Predicate(is_release), 2);
+        assert_ne!(second, third);
+
+        // Predicates are only removed by their id, and only for their actor.
+        assert!(table
+            .unsubscribe(Subscription::Predicate(is_release), 1)
+            .is_empty());
+        assert!(!table.unsubscribe_predicate(first, 2));
+        assert_eq!(table.route(&Event::Release(0)).as_slice(), &[1, 2]);
+
+        assert!(table.unsubscribe_predicate(first, 1));
+        assert!(!table.unsubscribe_predicate(first, 1));
+        assert!(table.unsubscribe_predicate(second, 2));
+        assert_eq!(table.predicate_count(2), 1);
+        assert_eq!(table.route(&Event::Release(0)).as_slice(), &[2]);
+
+        let mut commutator = Commutator::new();
+        let recorder = commutator
+            .attach(Box::new(Recorder {
+                subscription: Subscription::One(Signal::Tick),
+                log: Arc::default(),
+            }))
+            .id();
+        let release = commutator
+            .subscribe(recorder, Subscription::Predicate(is_release))
+            .unwrap();
+        assert_eq!(commutator.subscriptions().predicate_count(recorder), 1);
+        assert!(commutator.unsubscribe_predicate(recorder, release));
+        assert_eq!(commutator.subscriptions().predicate_count(recorder), 0);
+        assert_eq!(
+            commutator.subscriptions().subscriptions(recorder),
+            vec![Signal::Tick]
+        );
+    }
+
+    struct Recorder {
+        subscription: Subscription<Event>,
+        log: Arc<Mutex<Vec<(&'static str, u8)>>>,
+    }
+
+    impl Actor for Recorder {
+        type Message = Event;
+
+        fn initial_subscriptions(&self) -> Vec<Subscription<Event>> {
+            vec![self.subscription]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            let name = match self.subscription {
+                Subscription::All => "all",
+                Subscription::Group(group) => group,
+                _ => "other",
+            };
+            let value = match envelope.message {
+                Event::Press(value) | Event::Release(value) => value,
+                _ => 0,
+            };
+            self.log.lock().unwrap().push((name, value));
+        }
+    }
+
+    #[test]
+    fn commutator_routes_subscriptions() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let logger = commutator.attach(Box::new(Recorder {
+            subscription: Subscription::All,
+            log: log.clone(),
+        }));
+        commutator.attach(Box::new(Recorder {
+            subscription: Subscription::Group("input"),
+            log: log.clone(),
+        }));
+        commutator.publish(Event::Press(1));
+        commutator.publish(Event::Tick);
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("all", 1), ("input", 1), ("all", 0)]
+        );
+
+        assert!(commutator.unsubscribe(logger.id(), Subscription::All));
+        assert!(commutator
+            .subscriptions()
+            .subscriptions(logger.id())
+            .is_empty());
+        assert!(commutator.subscribe(0, Subscription::All).is_none());
+
+        // The sender of the commutator isn't handed to any actor, so it can't
+        // subscribe.
+        assert!(commutator.sender().subscribe(Subscription::All).is_none());
+    }
+
+    #[test]
+    fn subscription_filters() {
+        let mut table = SubscriptionTable::<Signal>::new();
+        table.subscribe(Subscription::Group("input"), 1);
+        table.subscribe(Subscription::Group("input"), 2);
+        table.set_filter(
+            Signal::Press,
+            2,
+            Box::new(|event| matches!(event, Event::Press(2))),
+        );
+        assert!(table.has_filter(Signal::Press, 2));
+        assert_eq!(table.route(&Event::Press(1)).as_slice(), &[1]);
+        assert_eq!(table.route(&Event::Press(2)).as_slice(), &[1, 2]);
+        assert_eq!(table.route(&Event::Release(1)).as_slice(), &[1, 2]);
+
+        assert!(table.clear_filter(Signal::Press, 2));
+        assert!(!table.clear_filter(Signal::Press, 2));
+        assert_eq!(table.route(&Event::Press(1)).as_slice(), &[1, 2]);
+    }
+
+    struct Button {
+        number: u8,
+        log: Arc<Mutex<Vec<(&'static str, u8)>>>,
+    }
+
+    impl Actor for Button {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Press]
+        }
+
+        fn subscription_filters(&self) -> Vec<SubscriptionFilter<Event>> {
+            let number = self.number;
+            vec![(
+                Signal::Press,
+                Box::new(move |event| matches!(event, Event::Press(n) if *n == number)),
+            )]
+        }
+
+        fn handle(&mut self, _: &Envelope<Event>) {
+            self.log.lock().unwrap().push(("button", self.number));
+        }
+    }
+
+    #[test]
+    fn commutator_filters_subscriptions() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        for number in 1..=3 {
+            commutator.attach(Box::new(Button {
+                number,
+                log: log.clone(),
+            }));
+        }
+        let button = commutator.actor_ids()[0];
+        commutator.publish(Event::Press(2));
+        commutator.publish(Event::Press(1));
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        assert_eq!(*log.lock().unwrap(), vec![("button", 2), ("button", 1)]);
+
+        assert!(commutator.set_filter(button, Signal::Press, Box::new(|_| false)));
+        assert!(commutator.clear_filter(button, Signal::Press));
+        assert!(!commutator.set_filter(0, Signal::Press, Box::new(|_| false)));
+    }
+
+    /// Handles a press once, until it is reset by a tick.
+    struct Latch {
+        sender: Option<Sender<Event>>,
+        log: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Actor for Latch {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Press, Signal::Tick]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            let sender = self.sender.as_ref().unwrap();
+            match envelope.message {
+                Event::Press(number) => {
+                    self.log.lock().unwrap().push(number);
+                    sender.set_filter(Signal::Press, Box::new(|_| false));
+                }
+                Event::Tick => sender.clear_filter(Signal::Press),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn actors_filter_their_own_subscriptions() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator.attach(Box::new(Latch {
+            sender: None,
+            log: log.clone(),
+        }));
+        // The filter commands are queued behind the messages that were
+        // published before, so every round ends with a stop.
+        for round in [
+            vec![Event::Press(1)],
+            vec![Event::Press(2), Event::Tick],
+            vec![Event::Press(3)],
+        ] {
+            for event in round {
+                commutator.publish(event);
+            }
+            commutator.publish(Event::Stop);
+            block_on(commutator.run());
+        }
+        assert_eq!(*log.lock().unwrap(), vec![1, 3]);
+    }
+}
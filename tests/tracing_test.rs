@@ -0,0 +1,191 @@
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, MessageType, Publisher, Sender};
+    use async_std::task::block_on;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event as TracingEvent, Metadata, Subscriber};
+    use tracing_core::span::Current;
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Ping,
+        Pong,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    #[derive(Clone, Debug)]
+    struct Span {
+        id: u64,
+        name: &'static str,
+        metadata: &'static Metadata<'static>,
+        parent: Option<u64>,
+        fields: Vec<String>,
+    }
+
+    impl Visit for Span {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    /// Subscriber that records the spans that are created on the test thread.
+    #[derive(Clone, Default)]
+    struct Spans {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<Vec<Span>>>,
+        stack: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Spans {
+        fn named(&self, name: &str) -> Vec<Span> {
+            let spans = self.spans.lock().unwrap();
+            spans
+                .iter()
+                .filter(|span| span.name == name)
+                .cloned()
+                .collect()
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let parent = if let Some(parent) = attributes.parent() {
+                Some(parent.into_u64())
+            } else if attributes.is_contextual() {
+                self.stack.lock().unwrap().last().copied()
+            } else {
+                None
+            };
+            let mut span = Span {
+                id,
+                name: attributes.metadata().name(),
+                metadata: attributes.metadata(),
+                parent,
+                fields: Vec::new(),
+            };
+            attributes.record(&mut span);
+            self.spans.lock().unwrap().push(span);
+            Id::from_u64(id)
+        }
+
+        fn record(&self, id: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            if let Some(span) = spans.iter_mut().find(|span| span.id == id.into_u64()) {
+                values.record(span);
+            }
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &TracingEvent<'_>) {}
+
+        fn enter(&self, id: &Id) {
+            self.stack.lock().unwrap().push(id.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.stack.lock().unwrap().pop();
+        }
+
+        fn current_span(&self) -> Current {
+            let id = match self.stack.lock().unwrap().last() {
+                Some(id) => *id,
+                None => return Current::none(),
+            };
+            let spans = self.spans.lock().unwrap();
+            let span = spans.iter().find(|span| span.id == id).unwrap();
+            Current::new(Id::from_u64(id), span.metadata)
+        }
+    }
+
+    /// Answers a ping with a pong from another thread through a deputy, and
+    /// stops the commutator when the pong arrives.
+    struct Player {
+        sender: Option<Sender<Event>>,
+    }
+
+    impl Publisher for Player {
+        type Message = Event;
+
+        fn sender(&self) -> &Sender<Event> {
+            self.sender.as_ref().unwrap()
+        }
+    }
+
+    impl Actor for Player {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping, Signal::Pong]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            match envelope.message {
+                Event::Ping => {
+                    let deputy = self.deputy().unwrap();
+                    thread::spawn(move || deputy.publish(Event::Pong));
+                }
+                Event::Pong => self.publish(Event::Stop),
+                Event::Stop => {}
+            }
+        }
+    }
+
+    #[test]
+    fn spans_per_dispatch() {
+        let spans = Spans::default();
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let player = commutator.attach(Box::new(Player { sender: None })).id();
+        commutator.publish(Event::Ping);
+        tracing::subscriber::with_default(spans.clone(), || block_on(commutator.run()));
+
+        let dispatches = spans.named("dispatch");
+        let handles = spans.named("handle");
+        assert_eq!(dispatches.len(), 2);
+        assert_eq!(handles.len(), 2);
+        let (ping, pong) = (&dispatches[0], &dispatches[1]);
+
+        assert_eq!(ping.parent, None);
+        assert!(ping.fields.contains(&String::from("message_type=\"Ping\"")));
+        assert!(ping.fields.contains(&String::from("origin=Anonymous")));
+        assert!(ping.fields.contains(&String::from("destination=All")));
+        assert!(ping.fields.contains(&format!("handlers=[{:?}]", player)));
+        assert_eq!(handles[0].parent, Some(ping.id));
+        assert!(handles[0].fields.contains(&format!("actor={}", player)));
+
+        // The pong was sent by a deputy on another thread, in the span of the
+        // ping handler.
+        assert_eq!(pong.parent, Some(handles[0].id));
+        assert!(pong.fields.contains(&format!("origin=Actor({:?})", player)));
+        assert_eq!(handles[1].parent, Some(pong.id));
+    }
+}
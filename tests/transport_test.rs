@@ -0,0 +1,272 @@
+#[cfg(all(test, feature = "transport", unix))]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{
+        Actor, ActorId, Commutator, Destination, Envelope, MessageType, Origin, Sender, Transport,
+    };
+    use async_std::future::timeout;
+    use async_std::task::block_on;
+    use serde::{Deserialize, Serialize};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    #[derive(Debug, MessageType, Serialize, Deserialize)]
+    #[message_type(name = "Signal", serde)]
+    pub enum Event {
+        Ping(u32),
+        Pong(u32),
+        Quit,
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    /// Answers every ping with a pong to the actor that sent it.
+    struct Echo {
+        sender: Option<Sender<Event>>,
+    }
+
+    impl Actor for Echo {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Ping]
+        }
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let (Event::Ping(n), Origin::Actor(from)) = (&envelope.message, envelope.origin) {
+                let reply = Envelope::new(
+                    Origin::Actor(self.id().unwrap()),
+                    Destination::Single(from),
+                    Event::Pong(*n),
+                );
+                self.sender.as_ref().unwrap().unbounded_send(reply).unwrap();
+            }
+        }
+    }
+
+    /// Publishes a ping when it is initialized. When the pong arrives, it
+    /// lets the remote commutator quit and stops its own commutator.
+    struct Asker {
+        sender: Option<Sender<Event>>,
+        pongs: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Actor for Asker {
+        type Message = Event;
+
+        fn on_attach(&mut self, sender: &Sender<Event>) {
+            self.sender = Some(sender.clone());
+        }
+
+        fn init(&mut self) {
+            let ping = Envelope::new(
+                Origin::Actor(self.id().unwrap()),
+                Destination::All,
+                Event::Ping(7),
+            );
+            self.sender.as_ref().unwrap().unbounded_send(ping).unwrap();
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let (Event::Pong(n), Origin::Actor(from)) = (&envelope.message, envelope.origin) {
+                self.pongs.lock().unwrap().push(*n);
+                let sender = self.sender.as_ref().unwrap();
+                let quit = Envelope::new(Origin::Anonymous, Destination::Single(from), Event::Quit);
+                sender.unbounded_send(quit).unwrap();
+                let stop = Envelope::new(Origin::Anonymous, Destination::All, Event::Stop);
+                sender.unbounded_send(stop).unwrap();
+            }
+        }
+    }
+
+    /// Takes up an actor id on the server.
+    struct Idle;
+
+    impl Actor for Idle {
+        type Message = Event;
+
+        fn handle(&mut self, _: &Envelope<Event>) {}
+    }
+
+    fn client_commutator() -> Commutator<Event> {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+    }
+
+    fn server_commutator() -> Commutator<Event> {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Quit => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+    }
+
+    fn echo() -> Box<Echo> {
+        Box::new(Echo { sender: None })
+    }
+
+    fn asker(pongs: &Arc<Mutex<Vec<u32>>>) -> Box<Asker> {
+        Box::new(Asker {
+            sender: None,
+            pongs: pongs.clone(),
+        })
+    }
+
+    /// Run a commutator on its own thread, and give it back when it stops.
+    fn spawn(mut commutator: Commutator<Event>) -> JoinHandle<Commutator<Event>> {
+        thread::spawn(move || {
+            block_on(timeout(Duration::from_secs(10), commutator.run()))
+                .expect("commutator didn't stop");
+            commutator
+        })
+    }
+
+    fn is_connected(commutator: &mut Commutator<Event>, id: ActorId) -> bool {
+        commutator
+            .get_actor::<Transport<Event>>(id)
+            .unwrap()
+            .is_connected()
+    }
+
+    #[test]
+    fn ping_pong_over_tcp() {
+        let pongs = Arc::new(Mutex::new(Vec::new()));
+        let transport = block_on(Transport::bind_tcp("127.0.0.1:0", vec![Signal::Ping])).unwrap();
+        let addr = transport.local_addr().unwrap();
+
+        let mut server = server_commutator();
+        server.attach(Box::new(transport));
+        server.attach(echo());
+
+        let mut client = client_commutator();
+        let client_transport = client
+            .attach(Box::new(Transport::connect_tcp(
+                addr.to_string(),
+                vec![Signal::Ping],
+            )))
+            .id();
+        client.attach(asker(&pongs));
+
+        let server = spawn(server);
+        let client = spawn(client);
+        let server = server.join().unwrap();
+        let mut client = client.join().unwrap();
+
+        assert_eq!(*pongs.lock().unwrap(), vec![7]);
+        assert!(is_connected(&mut client, client_transport));
+        drop(server);
+    }
+
+    #[test]
+    fn reconnect_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("armature-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pongs = Arc::new(Mutex::new(Vec::new()));
+
+        // The client is started before the server listens, and keeps trying
+        // to connect.
+        let mut client = client_commutator();
+        client.attach(Box::new(
+            Transport::connect_unix(&path, vec![Signal::Ping])
+                .with_reconnect_delay(Duration::from_millis(10)),
+        ));
+        client.attach(asker(&pongs));
+        let client_thread = spawn(client);
+
+        thread::sleep(Duration::from_millis(100));
+        let mut server = server_commutator();
+        server.attach(Box::new(
+            block_on(Transport::bind_unix(&path, vec![Signal::Ping])).unwrap(),
+        ));
+        server.attach(echo());
+        let server = spawn(server).join().unwrap();
+        let client = client_thread.join().unwrap();
+        assert_eq!(*pongs.lock().unwrap(), vec![7]);
+
+        // Replace the server, the client reconnects to the new one.
+        drop(server);
+        std::fs::remove_file(&path).unwrap();
+        let mut server = server_commutator();
+        server.attach(Box::new(
+            block_on(Transport::bind_unix(&path, vec![Signal::Ping])).unwrap(),
+        ));
+        server.attach(echo());
+        let client_thread = spawn(client);
+        spawn(server).join().unwrap();
+        client_thread.join().unwrap();
+        assert_eq!(*pongs.lock().unwrap(), vec![7, 7]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detach_proxies_when_peer_restarts() {
+        let pongs = Arc::new(Mutex::new(Vec::new()));
+        let transport = block_on(Transport::bind_tcp("127.0.0.1:0", vec![Signal::Ping])).unwrap();
+        let addr = transport.local_addr().unwrap();
+
+        let mut server = server_commutator();
+        server.attach(Box::new(transport));
+        let echo_id = server.attach(echo()).id();
+
+        let mut client = client_commutator();
+        let client_transport = client
+            .attach(Box::new(
+                Transport::connect_tcp(addr.to_string(), vec![Signal::Ping])
+                    .with_reconnect_delay(Duration::from_millis(10)),
+            ))
+            .id();
+        client.attach(asker(&pongs));
+
+        let server_thread = spawn(server);
+        let mut client = spawn(client).join().unwrap();
+        let server = server_thread.join().unwrap();
+        let transport = client
+            .get_actor::<Transport<Event>>(client_transport)
+            .unwrap();
+        let old_proxy = transport.proxy(echo_id).unwrap();
+        drop(server);
+
+        // The restarted server gives the echo another id, and its old id to
+        // another actor.
+        thread::sleep(Duration::from_millis(100));
+        let mut server = server_commutator();
+        server.attach(Box::new(
+            block_on(Transport::bind_tcp(addr, vec![Signal::Ping])).unwrap(),
+        ));
+        assert_eq!(server.attach(Box::new(Idle)).id(), echo_id);
+        let new_echo_id = server.attach(echo()).id();
+        let server_thread = spawn(server);
+        let mut client = spawn(client).join().unwrap();
+        // The connection is lost once the server is dropped.
+        let server = server_thread.join().unwrap();
+        assert_eq!(*pongs.lock().unwrap(), vec![7, 7]);
+
+        let transport = client
+            .get_actor::<Transport<Event>>(client_transport)
+            .unwrap();
+        assert_eq!(transport.proxy(echo_id), None);
+        let new_proxy = transport.proxy(new_echo_id).unwrap();
+        assert!(!client.actor_ids().contains(&old_proxy));
+        assert_eq!(client.children(client_transport), &[new_proxy]);
+        drop(server);
+    }
+}
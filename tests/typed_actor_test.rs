@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Commutator, Envelope, MessageType, Subscription, Typed, TypedActor};
+    use async_std::task::block_on;
+    use std::convert::TryFrom;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        Add(u32),
+        Say(String),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    /// Local message type of the adder.
+    pub struct Add(u32);
+
+    impl TryFrom<&Event> for Add {
+        type Error = ();
+
+        fn try_from(event: &Event) -> Result<Self, ()> {
+            match event {
+                Event::Add(value) => Ok(Add(*value)),
+                _ => Err(()),
+            }
+        }
+    }
+
+    /// Local message type of the speaker.
+    pub enum Speech {
+        Say(String),
+        Add(u32),
+    }
+
+    impl TryFrom<&Event> for Speech {
+        type Error = ();
+
+        fn try_from(event: &Event) -> Result<Self, ()> {
+            match event {
+                Event::Say(text) => Ok(Speech::Say(text.clone())),
+                Event::Add(value) => Ok(Speech::Add(*value)),
+                Event::Stop => Err(()),
+            }
+        }
+    }
+
+    struct Adder {
+        sum: u32,
+    }
+
+    impl TypedActor for Adder {
+        type Message = Event;
+        type Local = Add;
+
+        fn handle(&mut self, Add(value): Add, _: &Envelope<Event>) {
+            self.sum += value;
+        }
+    }
+
+    struct Speaker {
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TypedActor for Speaker {
+        type Message = Event;
+        type Local = Speech;
+
+        fn initial_subscriptions(&self) -> Vec<Subscription<Event>> {
+            vec![Subscription::One(Signal::Say)]
+        }
+
+        fn handle(&mut self, message: Speech, _: &Envelope<Event>) {
+            let line = match message {
+                Speech::Say(text) => text,
+                Speech::Add(value) => format!("add {}", value),
+            };
+            self.log.lock().unwrap().push(line);
+        }
+    }
+
+    #[test]
+    fn convert_messages_at_dispatch() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        let adder = commutator.attach_typed(Adder { sum: 0 });
+        let speaker = commutator.attach_typed(Speaker { log: log.clone() });
+
+        commutator.publish(Event::Add(1));
+        commutator.publish(Event::Say(String::from("hello")));
+        commutator.publish(Event::Add(2));
+        // Messages that don't convert are dropped.
+        adder.post(Event::Say(String::from("ignored"))).unwrap();
+        speaker.post(Event::Add(3)).unwrap();
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+
+        let adder = commutator.get_actor::<Typed<Adder>>(adder.id()).unwrap();
+        assert_eq!(adder.get_ref().sum, 3);
+        assert_eq!(*log.lock().unwrap(), vec!["hello", "add 3"]);
+    }
+}
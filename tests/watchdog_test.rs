@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+
+    use armature::commutator::InterceptResult;
+    use armature::{Actor, Commutator, Envelope, MessageType, Stall};
+    use async_std::task::block_on;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Debug, MessageType)]
+    #[message_type(name = "Signal")]
+    pub enum Event {
+        /// Sleep for the given number of milliseconds.
+        Work(u64),
+        Stop,
+    }
+
+    impl armature::Message for Event {
+        type MessageType = Signal;
+    }
+
+    impl armature::MessageType for Signal {
+        type Message = Event;
+    }
+
+    /// Blocks the commutator while it works.
+    struct Sleeper;
+
+    impl Actor for Sleeper {
+        type Message = Event;
+
+        fn default_subscriptions(&self) -> Vec<Signal> {
+            vec![Signal::Work]
+        }
+
+        fn handle(&mut self, envelope: &Envelope<Event>) {
+            if let Event::Work(millis) = envelope.message {
+                thread::sleep(Duration::from_millis(millis));
+            }
+        }
+    }
+
+    fn commutator() -> Commutator<Event> {
+        let mut commutator = Commutator::new();
+        commutator.set_interceptor(|_, message| match message {
+            Event::Stop => InterceptResult::Break,
+            message => InterceptResult::Pass(message),
+        });
+        commutator
+    }
+
+    #[test]
+    fn handlers_over_budget_are_counted() {
+        let mut commutator = commutator();
+        commutator.set_handle_budget(Some(Duration::from_millis(5)));
+        let strict = commutator.attach(Box::new(Sleeper)).id();
+        let lenient = commutator.attach(Box::new(Sleeper)).id();
+        assert!(commutator.set_actor_budget(lenient, Some(Duration::from_secs(10))));
+
+        commutator.publish(Event::Work(0));
+        commutator.publish(Event::Work(20));
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+
+        let metrics = commutator.metrics();
+        let over_budget: Vec<_> = metrics
+            .actors
+            .iter()
+            .map(|actor| (actor.id, actor.over_budget))
+            .collect();
+        assert_eq!(over_budget, vec![(strict, 1), (lenient, 0)]);
+    }
+
+    #[test]
+    fn watchdog_reports_a_stall_once() {
+        let stalls = Arc::new(Mutex::new(Vec::<Stall>::new()));
+        let mut commutator = commutator();
+        let sleeper = commutator.attach(Box::new(Sleeper)).id();
+        let watchdog = {
+            let stalls = stalls.clone();
+            commutator.watchdog(Duration::from_millis(20), move |stall| {
+                stalls.lock().unwrap().push(stall)
+            })
+        };
+
+        commutator.publish(Event::Work(1));
+        commutator.publish(Event::Work(200));
+        commutator.publish(Event::Stop);
+        block_on(commutator.run());
+        // The commutator is idle now, so there are no more stalls.
+        thread::sleep(Duration::from_millis(50));
+        drop(watchdog);
+
+        let stalls = stalls.lock().unwrap();
+        assert_eq!(stalls.len(), 1);
+        assert_eq!(stalls[0].actor, Some(sleeper));
+        assert!(stalls[0].duration >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn watchdog_reports_queued_packets_that_are_not_taken() {
+        let stalls = Arc::new(Mutex::new(Vec::<Stall>::new()));
+        let mut commutator = commutator();
+        commutator.attach(Box::new(Sleeper));
+        let watchdog = {
+            let stalls = stalls.clone();
+            commutator.watchdog(Duration::from_millis(20), move |stall| {
+                stalls.lock().unwrap().push(stall)
+            })
+        };
+
+        // The loop isn't polled, so the packet keeps waiting.
+        commutator.publish(Event::Work(0));
+        thread::sleep(Duration::from_millis(100));
+        drop(watchdog);
+
+        let stalls = stalls.lock().unwrap();
+        assert_eq!(stalls.len(), 1);
+        assert_eq!(stalls[0].actor, None);
+        assert!(stalls[0].duration >= Duration::from_millis(20));
+    }
+}